@@ -0,0 +1,127 @@
+// Timings of xicor against the original implementation, which sorted indices
+// by comparison and accumulated the ranks as floats. Run with `cargo bench`.
+use num_traits::float::FloatCore;
+use ordered_float::OrderedFloat;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use xicor::{xicor, xicorf};
+
+
+
+fn main() {
+    println!("{:>9} {:>5} {:>12} {:>12}", "n", "type", "baseline", "xicor");
+
+    for n in [10usize, 100, 1000, 10_000, 100_000, 1_000_000] {
+        // Roughly the same amount of work for every length
+        let reps = (10_000_000/(n*(n.ilog2() as usize+1))).max(3);
+        let perm: Vec<usize> = (0..n).map(|i| (i*2654435761)%n).collect();
+        let xf: Vec<f32> = perm.iter().map(|&i| i as f32/n as f32).collect();
+        let yf: Vec<f32> = xf.iter().map(|&x| (x*12.566).sin()).collect();
+        let xd: Vec<f64> = xf.iter().map(|&x| x as f64).collect();
+        let yd: Vec<f64> = yf.iter().map(|&y| y as f64).collect();
+        let xu: Vec<u32> = perm.iter().map(|&i| i as u32).collect();
+        let yu: Vec<u32> = perm.iter().map(|&i| ((i*i)%1000003) as u32).collect();
+        let xi: Vec<i64> = perm.iter().map(|&i| i as i64-5000).collect();
+        let yi: Vec<i64> = yu.iter().map(|&y| -3*y as i64).collect();
+
+        row("f32", reps, &xf, &yf, baselinef, xicorf);
+        row("f64", reps, &xd, &yd, baselinef, xicorf);
+        row("u32", reps, &xu, &yu, baseline, xicor);
+        row("i64", reps, &xi, &yi, baseline, xicor);
+    }
+}
+
+// Print the mean times taken by the baseline and xicor on the same data.
+fn row<T>(
+    name: &str,
+    reps: usize,
+    x: &[T],
+    y: &[T],
+    baseline: impl Fn(&[T], &[T]) -> f64,
+    xicor: impl Fn(&[T], &[T]) -> f64,
+) {
+    let n = x.len();
+    let baseline = time(reps, || baseline(x, y));
+    let xicor = time(reps, || xicor(x, y));
+
+    println!("{n:>9} {name:>5} {baseline:>12.3?} {xicor:>12.3?}");
+}
+
+// The mean time taken by a function over the given number of repetitions.
+fn time(reps: usize, mut f: impl FnMut() -> f64) -> Duration {
+    let start = Instant::now();
+
+    for _ in 0..reps { black_box(f()); }
+
+    start.elapsed()/reps as u32
+}
+
+fn baselinef<F: FloatCore>(x: &[F], y: &[F]) -> f64 {
+    // This is safe because OrderedFloat has transparent representation
+    let x: &[OrderedFloat<F>] = unsafe { std::mem::transmute(x) };
+    let y: &[OrderedFloat<F>] = unsafe { std::mem::transmute(y) };
+
+    baseline(x, y)
+}
+
+// The original implementation of xicor.
+fn baseline<T: Ord + Copy>(x: &[T], y: &[T]) -> f64 {
+    let idcs = argsort(x);
+    let y_ord = permute(y, &idcs);
+
+    let idcs = argsort(&y_ord);
+    let y_ascending = permute(&y_ord, &idcs);
+    let r_ascending = cumulative_lte(&y_ascending);
+    let l_ascending = cumulative_gte(&y_ascending);
+    let mut rs = vec![0.; x.len()];
+    let mut ls = vec![0.; x.len()];
+
+    for ((i, r), l) in idcs.into_iter().zip(r_ascending).zip(l_ascending) {
+        rs[i] = r as f64;
+        ls[i] = l as f64;
+    }
+
+    let rsum = rs.windows(2)
+        .map(|win| (win[0]-win[1]).abs())
+        .sum::<f64>();
+
+    let n = x.len() as f64;
+    let lsum = ls.into_iter()
+        .map(|l| l*(n-l))
+        .sum::<f64>();
+
+    1.-n*rsum/(2.*lsum)
+}
+
+fn argsort<T: Ord>(arr: &[T]) -> Vec<usize> {
+    let mut idcs: Vec<usize> = (0..arr.len()).collect();
+
+    idcs.sort_unstable_by_key(|&i| &arr[i]);
+    idcs
+}
+
+fn permute<T: Copy>(arr: &[T], idcs: &[usize]) -> Vec<T> {
+    idcs.iter()
+        .map(|&i| arr[i])
+        .collect()
+}
+
+fn cumulative_lte<T: PartialEq<T> + Copy>(arr: &[T]) -> Vec<usize> {
+    let mut counts: Vec<usize> = (1..=arr.len()).collect();
+
+    for i in (0..arr.len()-1).rev() {
+        if arr[i] == arr[i+1] { counts[i] = counts[i+1]; }
+    }
+
+    counts
+}
+
+fn cumulative_gte<T: PartialEq<T> + Copy>(arr: &[T]) -> Vec<usize> {
+    let mut counts: Vec<usize> = (1..=arr.len()).rev().collect();
+
+    for i in 0..arr.len()-1 {
+        if arr[i+1] == arr[i] { counts[i+1] = counts[i]; }
+    }
+
+    counts
+}
@@ -0,0 +1,264 @@
+use crate::jackknife::leave_one_out;
+use crate::normal;
+use crate::xicor::{as_ordered, ranks};
+use num_traits::float::FloatCore;
+use rand::Rng;
+
+
+
+/// A two-sided confidence interval.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ConfidenceInterval {
+    /// The lower bound of the interval.
+    pub lower: f64,
+    /// The upper bound of the interval.
+    pub upper: f64,
+}
+
+impl ConfidenceInterval {
+    /// Whether the interval contains the given value.
+    pub fn contains(&self, value: f64) -> bool {
+        self.lower <= value && value <= self.upper
+    }
+}
+
+/// Bootstrap confidence intervals for xi.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bootstrap {
+    /// The xi-correlation of the data, exactly as returned by [`xicor`].
+    ///
+    /// [`xicor`]: crate::xicor
+    pub xi: f64,
+    /// The standard deviation of the bootstrap replicates of xi, which
+    /// estimates the standard error of xi. This is `None` when fewer than two
+    /// replicates are left, as a standard deviation needs at least two.
+    pub std_error: Option<f64>,
+    /// The percentile interval, formed directly from the quantiles of the
+    /// bootstrap replicates.
+    pub percentile: ConfidenceInterval,
+    /// The basic (or reverse percentile) interval, which reflects the
+    /// percentile interval about xi.
+    pub basic: ConfidenceInterval,
+    /// The bias-corrected and accelerated (BCa) interval.
+    pub bca: ConfidenceInterval,
+    /// The BCa bias correction `z0`, derived from the fraction of replicates
+    /// below xi.
+    pub bias_correction: f64,
+    /// The BCa acceleration, derived from a jackknife over the data.
+    pub acceleration: f64,
+    /// The number of replicates used. Replicates for which xi is undefined,
+    /// which happens when every resampled y value is identical, are discarded.
+    /// If none are left, as when y is constant or no resamples were requested,
+    /// every interval and estimate other than xi and the standard error is
+    /// NaN.
+    pub resamples: usize,
+}
+
+/// Compute bootstrap confidence intervals for the xi-correlation of two
+/// floating-point sequences.
+///
+/// See [`xicor_bootstrap`] for details.
+///
+/// # Example
+///
+/// ```
+/// use rand::SeedableRng;
+/// use rand::rngs::StdRng;
+/// use xicor::xicorf_bootstrap;
+///
+/// let x: Vec<f64> = (0..200).map(|i| i as f64/200.).collect();
+/// let y: Vec<f64> = x.iter().map(|&x| (x*12.566).sin()).collect();
+/// let mut rng = StdRng::seed_from_u64(1);
+/// let boot = xicorf_bootstrap(&x, &y, 1000, 0.95, &mut rng);
+///
+/// assert!(boot.bca.lower > 0.9);
+/// ```
+pub fn xicorf_bootstrap<FX, FY, R>(
+    x: &[FX],
+    y: &[FY],
+    resamples: usize,
+    level: f64,
+    rng: &mut R,
+) -> Bootstrap
+where
+    FX: FloatCore,
+    FY: FloatCore,
+    R: Rng + ?Sized,
+{
+    xicor_bootstrap(as_ordered(x), as_ordered(y), resamples, level, rng)
+}
+
+/// Compute bootstrap confidence intervals for the xi-correlation of two
+/// sequences whose values are orderable (they implement [`Ord`]).
+///
+/// The `(x, y)` pairs are resampled with replacement `resamples` times, and xi
+/// is computed for each resample. From these replicates the percentile, basic
+/// and bias-corrected and accelerated (BCa) intervals are formed, each with
+/// the given confidence `level` (for example 0.95). The BCa acceleration is
+/// estimated with a jackknife over the original data. The randomness comes
+/// entirely from `rng`, so seeding it makes the intervals reproducible.
+///
+/// Be aware that resampling with replacement duplicates pairs, and duplicated
+/// pairs sit next to each other in x with identical y values. This makes the
+/// replicates of xi biased upwards, often strongly so for weak dependence. The
+/// BCa interval corrects for this bias as far as it can, but it cannot extend
+/// beyond the range of the replicates, so when the dependence is weak none of
+/// the intervals should be relied upon.
+///
+/// # Example
+///
+/// ```
+/// use rand::SeedableRng;
+/// use rand::rngs::StdRng;
+/// use xicor::xicor_bootstrap;
+///
+/// let x: Vec<u32> = (0..100).collect();
+/// let y: Vec<u32> = x.iter().map(|x| (x*x)%17).collect();
+/// let mut rng = StdRng::seed_from_u64(1);
+/// let boot = xicor_bootstrap(&x, &y, 1000, 0.9, &mut rng);
+///
+/// // The replicates are biased upwards, so the bias correction is negative
+/// assert!(boot.bias_correction < 0.);
+/// ```
+pub fn xicor_bootstrap<X, Y, R>(
+    x: &[X],
+    y: &[Y],
+    resamples: usize,
+    level: f64,
+    rng: &mut R,
+) -> Bootstrap
+where
+    X: Ord,
+    Y: Ord,
+    R: Rng + ?Sized,
+{
+    assert!(x.len() == y.len(), "x and y must have the same length");
+    assert!(0. < level && level < 1., "confidence level must be between 0 and 1");
+
+    let n = x.len();
+    let xi = ranks(x, y).xi();
+    let mut x_boot = Vec::with_capacity(n);
+    let mut y_boot = Vec::with_capacity(n);
+    let mut replicates = Vec::with_capacity(resamples);
+
+    for _ in 0..resamples {
+        x_boot.clear();
+        y_boot.clear();
+
+        for _ in 0..n {
+            let i = rng.gen_range(0..n);
+
+            x_boot.push(&x[i]);
+            y_boot.push(&y[i]);
+        }
+
+        let xi_boot = ranks(&x_boot, &y_boot).xi();
+
+        if !xi_boot.is_nan() { replicates.push(xi_boot); }
+    }
+
+    // Without any replicates, as when y is constant, there are no intervals
+    if replicates.is_empty() {
+        let nan = ConfidenceInterval { lower: f64::NAN, upper: f64::NAN };
+
+        return Bootstrap {
+            xi,
+            std_error: None,
+            percentile: nan,
+            basic: nan,
+            bca: nan,
+            bias_correction: f64::NAN,
+            acceleration: f64::NAN,
+            resamples: 0,
+        };
+    }
+
+    replicates.sort_unstable_by(f64::total_cmp);
+
+    let alpha = (1.-level)/2.;
+    let percentile = ConfidenceInterval {
+        lower: quantile(&replicates, alpha),
+        upper: quantile(&replicates, 1.-alpha),
+    };
+    let basic = ConfidenceInterval {
+        lower: 2.*xi-percentile.upper,
+        upper: 2.*xi-percentile.lower,
+    };
+
+    let bias_correction = bias_correction(&replicates, xi);
+    let acceleration = acceleration(&leave_one_out(x, y));
+    let adjust = |alpha: f64| {
+        let z = bias_correction+normal::quantile(alpha);
+
+        normal::cdf(bias_correction+z/(1.-acceleration*z))
+    };
+    let bca = ConfidenceInterval {
+        lower: quantile(&replicates, adjust(alpha)),
+        upper: quantile(&replicates, adjust(1.-alpha)),
+    };
+
+    let resamples = replicates.len();
+    let mean = replicates.iter().sum::<f64>()/resamples as f64;
+    let std_error = (resamples >= 2).then(|| {
+        let ss = replicates.iter().map(|r| (r-mean).powi(2)).sum::<f64>();
+
+        (ss/(resamples-1) as f64).sqrt()
+    });
+
+    Bootstrap {
+        xi,
+        std_error,
+        percentile,
+        basic,
+        bca,
+        bias_correction,
+        acceleration,
+        resamples,
+    }
+}
+
+// Linearly interpolated quantile of sorted data, where q is between 0 and 1.
+pub(super) fn quantile(sorted: &[f64], q: f64) -> f64 {
+    if sorted.is_empty() || q.is_nan() { return f64::NAN; }
+
+    let pos = q.clamp(0., 1.)*(sorted.len()-1) as f64;
+    let lo = pos.floor() as usize;
+    let hi = pos.ceil() as usize;
+
+    sorted[lo]+(pos-lo as f64)*(sorted[hi]-sorted[lo])
+}
+
+// The BCa bias correction z0, which is the normal quantile of the fraction of
+// replicates below the estimate. Replicates equal to the estimate count half,
+// and the fraction is kept at least half a replicate away from 0 and 1 so that
+// z0 remains finite.
+fn bias_correction(replicates: &[f64], estimate: f64) -> f64 {
+    let b = replicates.len() as f64;
+    let below = replicates.iter().filter(|&&r| r < estimate).count();
+    let equal = replicates.iter().filter(|&&r| r == estimate).count();
+    let frac = (below as f64+0.5*equal as f64)/b;
+
+    normal::quantile(frac.clamp(0.5/b, 1.-0.5/b))
+}
+
+// The BCa acceleration, estimated from the skewness of the jackknife values.
+// Values are undefined for subsamples in which y is constant, so like the
+// bootstrap replicates those are left out, and without at least two defined
+// values there is no skewness to estimate.
+fn acceleration(jackknife: &[f64]) -> f64 {
+    let values: Vec<f64> = jackknife.iter()
+        .copied()
+        .filter(|j| j.is_finite())
+        .collect();
+
+    if values.len() < 2 { return 0.; }
+
+    let mean = values.iter().sum::<f64>()/values.len() as f64;
+    let (sum2, sum3) = values.iter()
+        .map(|j| mean-j)
+        .fold((0., 0.), |(s2, s3), d| (s2+d*d, s3+d*d*d));
+
+    if sum2 == 0. { return 0.; }
+
+    sum3/(6.*sum2.powf(1.5))
+}
@@ -0,0 +1,210 @@
+#[cfg(feature = "serde")]
+use crate::error::XiError;
+use std::mem;
+
+
+
+// The ratio between the capacities of consecutive levels, as in the KLL sketch
+// of Karnin, Lang and Liberty.
+const SHRINK: f64 = 2./3.;
+
+// A deterministic quantile sketch made of a stack of compactors. Level h holds
+// values of weight 2^h. The top level has capacity k, and each level below it
+// has SHRINK times the capacity of the one above, but never less than 2.
+// Whenever the sketch holds more values than the total capacity of its levels,
+// the lowest level at or over its own capacity is sorted and every other value
+// is promoted to the next level with double the weight. The total capacity is
+// below 3k plus 2 for each of the at most 64 levels, so memory is bounded
+// however many values are pushed.
+//
+// Each compaction at level h moves the number of values below any bound by at
+// most 2^h, so the sum of those weights bounds the error of every rank query.
+// While nothing has been compacted, every rank is exact.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(
+    try_from = "CompactorParts<T>",
+    bound(deserialize = "T: Ord + serde::Deserialize<'de>"),
+))]
+pub(super) struct Compactor<T> {
+    levels: Vec<Vec<T>>,
+    // The capacity of the top level
+    k: usize,
+    // The number of values held, and the total capacity of the levels, which
+    // are recomputed rather than serialised
+    #[cfg_attr(feature = "serde", serde(skip))]
+    held: usize,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    capacity: usize,
+    len: u64,
+    error: u64,
+    // Which half of the next compaction at each level is kept. Alternating
+    // means that successive errors tend to cancel rather than accumulate
+    odd: Vec<bool>,
+}
+
+impl<T: Ord> Compactor<T> {
+    pub(super) fn new(k: usize) -> Self {
+        let k = k.max(2);
+
+        Self {
+            levels: vec![vec![]],
+            k,
+            held: 0,
+            capacity: k,
+            len: 0,
+            error: 0,
+            odd: vec![false],
+        }
+    }
+
+    // The number of values pushed.
+    pub(super) fn len(&self) -> u64 {
+        self.len
+    }
+
+    // A bound on the difference between the true and estimated number of
+    // values below any bound.
+    pub(super) fn error(&self) -> u64 {
+        self.error
+    }
+
+    pub(super) fn push(&mut self, value: T) {
+        self.levels[0].push(value);
+        self.len += 1;
+        self.held += 1;
+        self.compress();
+    }
+
+    // Absorb a sketch of other values, after which this sketches the values of
+    // both. The error bounds add, since each bounds the error of its own
+    // values below any bound.
+    pub(super) fn merge(&mut self, other: Self) {
+        for (h, (level, odd)) in other.levels.into_iter().zip(other.odd).enumerate() {
+            if h == self.levels.len() {
+                self.levels.push(vec![]);
+                self.odd.push(odd);
+            }
+
+            self.held += level.len();
+            self.levels[h].extend(level);
+        }
+
+        self.len += other.len;
+        self.error += other.error;
+        self.capacity = self.total_capacity();
+        self.compress();
+    }
+
+    // The estimated number of values below the given bound.
+    pub(super) fn count_below(&self, bound: &T) -> u128 {
+        self.levels.iter()
+            .enumerate()
+            .map(|(h, level)| level.iter().filter(|&v| v < bound).count() as u128*(1 << h))
+            .sum()
+    }
+
+    // Every value held along with its weight, in ascending order of value.
+    pub(super) fn weighted(&self) -> Vec<(&T, u128)> {
+        let mut items: Vec<(&T, u128)> = self.levels.iter()
+            .enumerate()
+            .flat_map(|(h, level)| level.iter().map(move |v| (v, 1 << h)))
+            .collect();
+
+        items.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        items
+    }
+
+    fn level_capacity(&self, h: usize) -> usize {
+        let depth = (self.levels.len()-1-h) as i32;
+
+        ((self.k as f64*SHRINK.powi(depth)).ceil() as usize).max(2)
+    }
+
+    fn total_capacity(&self) -> usize {
+        (0..self.levels.len()).map(|h| self.level_capacity(h)).sum()
+    }
+
+    // Compact levels until the values held fit in the total capacity. Some
+    // level must be over its own capacity while they do not.
+    fn compress(&mut self) {
+        while self.held > self.capacity {
+            let h = (0..self.levels.len())
+                .find(|&h| self.levels[h].len() >= self.level_capacity(h))
+                .unwrap();
+
+            self.compact(h);
+        }
+    }
+
+    fn compact(&mut self, h: usize) {
+        let mut level = mem::take(&mut self.levels[h]);
+        let skip = self.odd[h] as usize;
+
+        if h+1 == self.levels.len() {
+            self.levels.push(vec![]);
+            self.odd.push(false);
+            self.capacity = self.total_capacity();
+        }
+
+        level.sort_unstable();
+
+        // An odd value out stays behind, which does not affect the error
+        if level.len()%2 == 1 { self.levels[h].push(level.pop().unwrap()); }
+
+        self.held -= level.len()/2;
+        self.odd[h] = !self.odd[h];
+        self.levels[h+1].extend(level.into_iter().skip(skip).step_by(2));
+        self.error += 1 << h;
+    }
+}
+
+// The serialised fields of a compactor, which are checked for consistency
+// before a compactor is built from them.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct CompactorParts<T> {
+    levels: Vec<Vec<T>>,
+    k: usize,
+    len: u64,
+    error: u64,
+    odd: Vec<bool>,
+}
+
+#[cfg(feature = "serde")]
+impl<T: Ord> TryFrom<CompactorParts<T>> for Compactor<T> {
+    type Error = XiError;
+
+    fn try_from(parts: CompactorParts<T>) -> Result<Self, XiError> {
+        let CompactorParts { levels, k, len, error, odd } = parts;
+
+        // The weights of the values held always add up to the number pushed
+        let weight = levels.iter()
+            .enumerate()
+            .try_fold(0u128, |total, (h, level)| {
+                total.checked_add(level.len() as u128*1u128.checked_shl(h as u32)?)
+            });
+        let valid = k >= 2
+            && !levels.is_empty()
+            && levels.len() <= 64
+            && levels.len() == odd.len()
+            && weight == Some(len as u128);
+
+        if !valid { return Err(XiError::InvalidSummary); }
+
+        let mut compactor = Self {
+            held: levels.iter().map(Vec::len).sum(),
+            capacity: 0,
+            levels,
+            k,
+            len,
+            error,
+            odd,
+        };
+
+        compactor.capacity = compactor.total_capacity();
+        compactor.compress();
+
+        Ok(compactor)
+    }
+}
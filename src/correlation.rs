@@ -0,0 +1,386 @@
+use crate::bootstrap::{xicor_bootstrap, ConfidenceInterval};
+use crate::error::XiError;
+use crate::exact::{xicor_exact_test, MAX_EXACT_N};
+use crate::independence::asymptotic_test;
+use crate::jackknife::xicor_jackknife;
+use crate::missing::NanPolicy;
+use crate::permutation::{xicor_permutation_test, PermutationOptions};
+use crate::xicor::{
+    argsort_ties, as_ordered, check_lengths, expected_rank_diff_sum, permute,
+    ranks_ordered, TiePolicy,
+};
+use num_traits::float::FloatCore;
+use ordered_float::OrderedFloat;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+
+
+/// How the p-value of an [`XiCorrelation`] is computed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PValueMethod {
+    /// The asymptotic normal test of [`xicor_test`].
+    ///
+    /// [`xicor_test`]: crate::xicor_test
+    Asymptotic,
+    /// The permutation test of [`xicor_permutation_test`], drawing random
+    /// numbers from the seed of the [`XiCorrelation`].
+    Permutation(PermutationOptions),
+    /// The exact test of [`xicor_exact_test`], which is only available for up
+    /// to [`MAX_EXACT_N`] pairs.
+    ///
+    /// [`MAX_EXACT_N`]: crate::MAX_EXACT_N
+    Exact,
+}
+
+/// Which bootstrap interval an [`XiCorrelation`] reports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IntervalMethod {
+    /// The percentile interval.
+    Percentile,
+    /// The basic (or reverse percentile) interval.
+    Basic,
+    /// The bias-corrected and accelerated (BCa) interval.
+    Bca,
+}
+
+/// A configurable calculation of the xi-correlation, along with whatever
+/// inference about it is required.
+///
+/// Every option has a default matching [`xicor`], so only the options that
+/// differ need to be set. The calculation itself is performed by
+/// [`compute`](Self::compute) or, for floating-point data,
+/// [`computef`](Self::computef).
+///
+/// [`xicor`]: crate::xicor
+///
+/// # Example
+///
+/// ```
+/// use xicor::{IntervalMethod, PValueMethod, TiePolicy, XiCorrelation};
+///
+/// let x: Vec<u32> = (0..100).map(|i| i/2).collect();
+/// let y: Vec<u32> = (0..100).map(|i| (i*i)%37).collect();
+/// let result = XiCorrelation::new()
+///     .ties(TiePolicy::Expected)
+///     .p_value(PValueMethod::Asymptotic)
+///     .interval(IntervalMethod::Percentile, 0.95)
+///     .seed(7)
+///     .compute(&x, &y)
+///     .unwrap();
+///
+/// assert_eq!(result.n, 100);
+/// assert_eq!(result.ties_x, 50);
+/// assert!(result.p_value.unwrap() < 0.05);
+/// assert!(result.interval.unwrap().lower > 0.);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct XiCorrelation {
+    ties: TiePolicy,
+    nan: NanPolicy,
+    normalise: bool,
+    std_error: bool,
+    p_value: Option<PValueMethod>,
+    interval: Option<(IntervalMethod, f64)>,
+    resamples: usize,
+    seed: u64,
+}
+
+/// The outcome of an [`XiCorrelation`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct XiResult {
+    /// The xi-correlation of the data.
+    pub xi: f64,
+    /// The normalised xi-correlation, as returned by [`xicor_norm`].
+    ///
+    /// [`xicor_norm`]: crate::xicor_norm
+    pub xi_norm: f64,
+    /// Whether the standard error and interval refer to the normalised
+    /// statistic rather than the raw one.
+    pub normalised: bool,
+    /// The number of pairs used.
+    pub n: usize,
+    /// The number of pairs dropped because a value was NaN.
+    pub dropped: usize,
+    /// The number of x values equal to some other, earlier x value, i.e. `n`
+    /// minus the number of distinct x values.
+    pub ties_x: usize,
+    /// The number of y values equal to some other, earlier y value.
+    pub ties_y: usize,
+    /// The jackknife standard error, if requested.
+    pub std_error: Option<f64>,
+    /// The p-value for the null hypothesis of independence, if requested.
+    pub p_value: Option<f64>,
+    /// The bootstrap confidence interval, if requested.
+    pub interval: Option<ConfidenceInterval>,
+}
+
+impl XiResult {
+    /// The statistic selected by the normalisation option: `xi_norm` if it
+    /// was set, and `xi` otherwise.
+    pub fn statistic(&self) -> f64 {
+        match self.normalised {
+            true => self.xi_norm,
+            false => self.xi,
+        }
+    }
+}
+
+impl Default for XiCorrelation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl XiCorrelation {
+    /// Calculate plain xi, exactly as [`xicor`] does, with no inference.
+    ///
+    /// [`xicor`]: crate::xicor
+    pub fn new() -> Self {
+        Self {
+            ties: TiePolicy::Stable,
+            nan: NanPolicy::Error,
+            normalise: false,
+            std_error: false,
+            p_value: None,
+            interval: None,
+            resamples: 1000,
+            seed: 0,
+        }
+    }
+
+    /// Set how pairs with tied x values are ordered.
+    pub fn ties(self, ties: TiePolicy) -> Self {
+        Self { ties, ..self }
+    }
+
+    /// Set how NaN values are handled by [`computef`](Self::computef).
+    pub fn nan(self, nan: NanPolicy) -> Self {
+        Self { nan, ..self }
+    }
+
+    /// Report the standard error and interval for the normalised statistic
+    /// rather than the raw one.
+    pub fn normalise(self, normalise: bool) -> Self {
+        Self { normalise, ..self }
+    }
+
+    /// Estimate the standard error of the statistic with the jackknife.
+    pub fn std_error(self, std_error: bool) -> Self {
+        Self { std_error, ..self }
+    }
+
+    /// Compute a p-value for independence with the given method.
+    pub fn p_value(self, method: PValueMethod) -> Self {
+        Self { p_value: Some(method), ..self }
+    }
+
+    /// Compute a bootstrap confidence interval with the given method and
+    /// confidence level.
+    pub fn interval(self, method: IntervalMethod, level: f64) -> Self {
+        Self { interval: Some((method, level)), ..self }
+    }
+
+    /// Set the number of bootstrap resamples drawn for the interval. The
+    /// default is 1000.
+    pub fn resamples(self, resamples: usize) -> Self {
+        Self { resamples, ..self }
+    }
+
+    /// Set the seed of the ChaCha8 generator used by the permutation test and
+    /// the bootstrap. The default is 0.
+    pub fn seed(self, seed: u64) -> Self {
+        Self { seed, ..self }
+    }
+
+    /// Perform the calculation on two floating-point sequences, handling NaN
+    /// according to the NaN policy.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use xicor::{NanPolicy, XiCorrelation};
+    ///
+    /// let x = [0.1, 0.5, 0.2, 0.8, f64::NAN, 0.9];
+    /// let y = [1.0, 2.5, 1.5, 4.0, 3.0, 4.5];
+    /// let result = XiCorrelation::new()
+    ///     .nan(NanPolicy::Drop)
+    ///     .computef(&x, &y)
+    ///     .unwrap();
+    ///
+    /// assert_eq!(result.n, 5);
+    /// assert_eq!(result.dropped, 1);
+    /// ```
+    pub fn computef<FX, FY>(&self, x: &[FX], y: &[FY]) -> Result<XiResult, XiError>
+    where
+        FX: FloatCore,
+        FY: FloatCore,
+    {
+        check_lengths(x, y)?;
+
+        let has_nan = || x.iter().any(|v| v.is_nan()) || y.iter().any(|v| v.is_nan());
+
+        match self.nan {
+            NanPolicy::Error if has_nan() => Err(XiError::NaN),
+            NanPolicy::Propagate if has_nan() => Ok(self.nan_result(x.len())),
+            NanPolicy::Drop => {
+                let (xs, ys): (Vec<_>, Vec<_>) = x.iter()
+                    .zip(y)
+                    .filter(|(x, y)| !x.is_nan() && !y.is_nan())
+                    .map(|(&x, &y)| (OrderedFloat(x), OrderedFloat(y)))
+                    .unzip();
+                let result = self.compute(&xs, &ys)?;
+
+                Ok(XiResult { dropped: x.len()-xs.len(), ..result })
+            },
+            NanPolicy::Lowest => {
+                let xs: Vec<_> = x.iter().map(nan_lowest_key).collect();
+                let ys: Vec<_> = y.iter().map(nan_lowest_key).collect();
+
+                self.compute(&xs, &ys)
+            },
+            _ => self.compute(as_ordered(x), as_ordered(y)),
+        }
+    }
+
+    /// Perform the calculation on two sequences whose values are orderable
+    /// (they implement [`Ord`]).
+    ///
+    /// Any p-value or interval is computed with the pairs arranged according
+    /// to the tie policy, so it is consistent with the reported xi. Under
+    /// [`TiePolicy::Expected`], only xi itself is averaged over the orderings
+    /// of tied x values, while inference uses the input order.
+    ///
+    /// # Errors
+    ///
+    /// If `x` and `y` differ in length, every y value is the same, there are
+    /// too few pairs (fewer than three when normalising), or the exact p-value
+    /// is requested for more than [`MAX_EXACT_N`] pairs.
+    ///
+    /// # Panics
+    ///
+    /// If the confidence level is not between 0 and 1.
+    ///
+    /// [`MAX_EXACT_N`]: crate::MAX_EXACT_N
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use xicor::{xicor_exact_test, PValueMethod, XiCorrelation};
+    ///
+    /// let x = [1, 2, 3, 4, 5, 6, 7];
+    /// let y = [3, 1, 4, 1, 5, 9, 2];
+    /// let result = XiCorrelation::new()
+    ///     .p_value(PValueMethod::Exact)
+    ///     .compute(&x, &y)
+    ///     .unwrap();
+    ///
+    /// assert_eq!(result.ties_y, 1);
+    /// assert_eq!(result.p_value, Some(xicor_exact_test(&x, &y).p_value));
+    /// ```
+    pub fn compute<X: Ord, Y: Ord>(&self, x: &[X], y: &[Y]) -> Result<XiResult, XiError> {
+        check_lengths(x, y)?;
+
+        let exact = matches!(self.p_value, Some(PValueMethod::Exact));
+
+        if exact && x.len() > MAX_EXACT_N {
+            return Err(XiError::TooManySamples { n: x.len() });
+        }
+
+        // The limit of xi is 0 for two pairs, so it cannot be normalised
+        if self.normalise && x.len() < 3 {
+            return Err(XiError::TooFewSamples { n: x.len() });
+        }
+
+        let idcs = argsort_ties(x, self.ties);
+        let y_ord = permute(y, &idcs);
+        let ranks = ranks_ordered(&y_ord);
+        let mut xi = ranks.try_xi()?;
+
+        // Only the expected tie policy and inference need x itself in order,
+        // so the plain coefficient never builds it
+        let x_ord = || permute(x, &idcs);
+
+        if self.ties == TiePolicy::Expected {
+            xi = ranks.xi_with_sum(expected_rank_diff_sum(&x_ord(), &ranks.rs));
+        }
+
+        let n = x.len();
+        let nf = n as f64;
+
+        // idcs sorts x, so tied values are adjacent in it
+        let ties_x = idcs.windows(2).filter(|win| x[win[0]] == x[win[1]]).count();
+        let ties_y = n-ranks.distinct;
+
+        let lim = (nf-2.)/(nf+1.);
+        let scale = match self.normalise {
+            true => 1./lim,
+            false => 1.,
+        };
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+
+        let std_error = self.std_error.then(|| {
+            scale*xicor_jackknife(&x_ord(), &y_ord).std_error
+        });
+
+        let p_value = self.p_value.map(|method| match method {
+            PValueMethod::Asymptotic => asymptotic_test(xi, ranks).p_value,
+            PValueMethod::Permutation(options) => {
+                xicor_permutation_test(&x_ord(), &y_ord, options, &mut rng).p_value
+            },
+            PValueMethod::Exact => xicor_exact_test(&x_ord(), &y_ord).p_value,
+        });
+
+        let interval = self.interval.map(|(method, level)| {
+            let boot = xicor_bootstrap(&x_ord(), &y_ord, self.resamples, level, &mut rng);
+            let interval = match method {
+                IntervalMethod::Percentile => boot.percentile,
+                IntervalMethod::Basic => boot.basic,
+                IntervalMethod::Bca => boot.bca,
+            };
+
+            ConfidenceInterval {
+                lower: scale*interval.lower,
+                upper: scale*interval.upper,
+            }
+        });
+
+        Ok(XiResult {
+            xi,
+            xi_norm: xi/lim,
+            normalised: self.normalise,
+            n,
+            dropped: 0,
+            ties_x,
+            ties_y,
+            std_error,
+            p_value,
+            interval,
+        })
+    }
+
+    // The result for n pairs containing NaN under the propagating policy, with
+    // every requested quantity NaN.
+    fn nan_result(&self, n: usize) -> XiResult {
+        let nan = ConfidenceInterval { lower: f64::NAN, upper: f64::NAN };
+
+        XiResult {
+            xi: f64::NAN,
+            xi_norm: f64::NAN,
+            normalised: self.normalise,
+            n,
+            dropped: 0,
+            ties_x: 0,
+            ties_y: 0,
+            std_error: self.std_error.then_some(f64::NAN),
+            p_value: self.p_value.map(|_| f64::NAN),
+            interval: self.interval.map(|_| nan),
+        }
+    }
+}
+
+// Pairing each value with whether it is a number sorts NaN below every number,
+// with all NaNs tied.
+fn nan_lowest_key<F: FloatCore>(v: &F) -> (bool, OrderedFloat<F>) {
+    (!v.is_nan(), OrderedFloat(*v))
+}
@@ -0,0 +1,108 @@
+use crate::xicor::{xicor, XiRatio};
+
+
+
+// The largest range of values for which buckets are always allocated. Above
+// this, counting is only used if the range is no more than a small multiple of
+// the number of points, so that memory stays O(n).
+const COUNTING_MAX_RANGE: u64 = 1 << 16;
+
+/// Calculate the xi-correlation of two integer sequences with a small range of
+/// values, using counting sorts rather than comparison sorts.
+///
+/// Data such as ratings, bins or quantised sensor readings often takes only a
+/// few hundred or thousand distinct values. In that case both sorts can be
+/// done by counting, and the rank quantities `r_i` and `l_i` read directly from
+/// a cumulative histogram of y, so xi is computed in `O(n + range)` time and
+/// memory, where `range` is the difference between the largest and smallest
+/// values. The result is exactly that of [`xicor`].
+///
+/// If the range of either sequence is larger than both 65536 and twice the
+/// number of points, the counting approach would waste memory, so [`xicor`]
+/// is used instead.
+///
+/// [`xicor`]: crate::xicor
+///
+/// # Panics
+///
+/// If `x` and `y` differ in length.
+///
+/// # Example
+///
+/// ```
+/// use xicor::{xicor, xicor_counting};
+///
+/// let x: Vec<u8> = (0..1000).map(|i| (i%256) as u8).collect();
+/// let y: Vec<u16> = x.iter().map(|&x| (x as u16*x as u16)%300).collect();
+///
+/// assert_eq!(xicor_counting(&x, &y), xicor(&x, &y));
+/// ```
+pub fn xicor_counting<X, Y>(x: &[X], y: &[Y]) -> f64
+where
+    X: Copy + Ord + Into<i64>,
+    Y: Copy + Ord + Into<i64>,
+{
+    assert!(x.len() == y.len(), "x and y must have the same length");
+
+    let n = x.len();
+    let max_range = COUNTING_MAX_RANGE.max(2*n as u64);
+    let (Some((kx, x_range)), Some((ky, y_range))) = (
+        offsets(x, max_range),
+        offsets(y, max_range),
+    ) else {
+        return xicor(x, y);
+    };
+
+    // Stable counting sort of the indices by x
+    let mut starts = vec![0; x_range+1];
+
+    for &k in &kx { starts[k] += 1; }
+
+    let mut offset = 0;
+
+    for start in &mut starts {
+        (*start, offset) = (offset, offset+*start);
+    }
+
+    let mut x_order = vec![0; n];
+
+    for (i, &k) in kx.iter().enumerate() {
+        x_order[starts[k]] = i;
+        starts[k] += 1;
+    }
+
+    // below[v] is the number of y values below v, so a y value of v has
+    // r = below[v+1] and l = n - below[v]
+    let mut below = vec![0usize; y_range+2];
+
+    for &k in &ky { below[k+1] += 1; }
+    for v in 1..below.len() { below[v] += below[v-1]; }
+
+    let rsum = x_order.windows(2)
+        .map(|win| below[ky[win[0]]+1].abs_diff(below[ky[win[1]]+1]) as u128)
+        .sum::<u128>();
+    let lsum = ky.iter()
+        .map(|&k| (n-below[k]) as u128*below[k] as u128)
+        .sum::<u128>();
+
+    XiRatio::new(n, rsum, lsum).to_f64()
+}
+
+// Subtract the minimum from every value, returning the offsets along with the
+// range of the values, or None if there are no values or the range is too big.
+fn offsets<T>(arr: &[T], max_range: u64) -> Option<(Vec<usize>, usize)>
+where
+    T: Copy + Into<i64>,
+{
+    let min = arr.iter().map(|&v| v.into()).min()?;
+    let max = arr.iter().map(|&v| v.into()).max()?;
+    let range = max.abs_diff(min);
+
+    if range > max_range { return None; }
+
+    let offsets = arr.iter()
+        .map(|&v| v.into().abs_diff(min) as usize)
+        .collect();
+
+    Some((offsets, range as usize))
+}
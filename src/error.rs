@@ -0,0 +1,62 @@
+use crate::exact::MAX_EXACT_N;
+use std::fmt;
+
+
+
+/// The reasons xi cannot be computed for a dataset.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum XiError {
+    /// The x and y sequences have different lengths.
+    LengthMismatch {
+        /// The length of the x sequence.
+        x: usize,
+        /// The length of the y sequence.
+        y: usize,
+    },
+    /// There are too few pairs for the statistic to be defined. Xi requires
+    /// at least 2 pairs, and normalised xi at least 3.
+    TooFewSamples {
+        /// The number of pairs given.
+        n: usize,
+    },
+    /// There are too many pairs for the exact null distribution, which is
+    /// limited to [`MAX_EXACT_N`] pairs.
+    ///
+    /// [`MAX_EXACT_N`]: crate::MAX_EXACT_N
+    TooManySamples {
+        /// The number of pairs given.
+        n: usize,
+    },
+    /// Every y value is the same, so xi is undefined (the denominator of its
+    /// formula is zero).
+    ConstantY,
+    /// A NaN was found in the x or y values.
+    NaN,
+    /// A deserialised summary or sketch is inconsistent, so it cannot have
+    /// been built by this crate.
+    InvalidSummary,
+}
+
+impl fmt::Display for XiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::LengthMismatch { x, y } => write!(
+                f, "x and y must have the same length, but have lengths {x} and {y}"
+            ),
+            Self::TooFewSamples { n } => write!(
+                f, "too few samples for xi to be defined ({n} were given)"
+            ),
+            Self::TooManySamples { n } => write!(
+                f,
+                "the exact test is limited to {MAX_EXACT_N} samples ({n} were given)"
+            ),
+            Self::ConstantY => write!(f, "xi is undefined when y is constant"),
+            Self::NaN => write!(f, "the data contains NaN"),
+            Self::InvalidSummary => write!(
+                f, "the summary is inconsistent, so was not built by this crate"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for XiError {}
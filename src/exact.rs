@@ -0,0 +1,295 @@
+use crate::xicor::{
+    argsort, as_ordered, cumulative_gte, cumulative_lte, permute, rank_diff_sum,
+    ranks,
+};
+use num_traits::float::FloatCore;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+
+
+/// The largest number of points for which the exact null distribution can be
+/// computed. Enumerating the orderings takes time proportional to `n!`, which
+/// is already several million at this limit.
+pub const MAX_EXACT_N: usize = 10;
+
+// The null distributions for y without ties, which are by far the most common,
+// precomputed for every n up to MAX_EXACT_N. CONTINUOUS[n][s] is the number of
+// permutations of n distinct values whose sum of absolute rank differences
+// is s.
+const CONTINUOUS: [&[u64]; MAX_EXACT_N+1] = [
+    &[1],
+    &[1],
+    &[0, 2],
+    &[0, 0, 2, 4],
+    &[0, 0, 0, 2, 4, 12, 4, 2],
+    &[0, 0, 0, 0, 2, 4, 14, 32, 18, 28, 14, 8],
+    &[0, 0, 0, 0, 0, 2, 4, 16, 36, 92, 68, 128, 92, 122, 72, 64, 16, 8],
+    &[
+        0, 0, 0, 0, 0, 0, 2, 4, 18, 40, 112, 240, 256, 448, 438, 668, 502, 696,
+        480, 496, 264, 240, 88, 48,
+    ],
+    &[
+        0, 0, 0, 0, 0, 0, 0, 2, 4, 20, 44, 134, 288, 696, 776, 1566, 1620, 2788,
+        2524, 3914, 3192, 4544, 3376, 4056, 2720, 2960, 1776, 1712, 816, 576,
+        144, 72,
+    ],
+    &[
+        0, 0, 0, 0, 0, 0, 0, 0, 2, 4, 22, 48, 158, 340, 874, 1800, 2574, 4732,
+        5922, 9872, 10786, 16732, 16598, 24296, 21968, 30288, 25480, 32176,
+        24888, 29712, 21992, 23616, 15472, 16032, 9504, 8352, 3960, 3024, 1080,
+        576,
+    ],
+    &[
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 4, 24, 52, 184, 396, 1080, 2244, 5220,
+        7316, 14952, 18716, 34440, 38724, 65880, 69020, 109682, 107480, 162064,
+        149888, 214176, 187648, 251872, 211280, 269568, 215184, 260640, 198960,
+        225608, 163104, 175536, 116064, 117432, 73296, 66096, 37872, 31176,
+        13248, 9216, 2304, 1152,
+    ],
+];
+
+// The precomputed null distributions, built on first use.
+static TABLES: OnceLock<Vec<Arc<NullDistribution>>> = OnceLock::new();
+
+// Null distributions which have already been computed, keyed by the ascending
+// r_i values of y. These encode both n and the pattern of ties in y, which
+// together determine the distribution completely.
+static CACHE: OnceLock<Mutex<HashMap<Vec<usize>, Arc<NullDistribution>>>> = OnceLock::new();
+
+/// The exact distribution of xi under the hypothesis that x and y are
+/// independent, for a fixed set of y values.
+///
+/// Under independence every arrangement of the y values relative to the x
+/// values is equally likely. The distribution of xi therefore depends only on
+/// the number of points and the pattern of ties among the y values.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NullDistribution {
+    n: usize,
+    lsum: usize,
+    // counts[s] is the number of distinct arrangements whose sum of absolute
+    // rank differences is s
+    counts: Vec<u64>,
+    total: u64,
+}
+
+impl NullDistribution {
+    /// The number of points.
+    pub fn n(&self) -> usize {
+        self.n
+    }
+
+    /// The values xi can take, in descending order, each paired with its
+    /// probability.
+    pub fn support(&self) -> Vec<(f64, f64)> {
+        self.counts.iter()
+            .enumerate()
+            .filter(|&(_, &count)| count > 0)
+            .map(|(s, &count)| (self.xi(s), count as f64/self.total as f64))
+            .collect()
+    }
+
+    /// The probability that xi is at least the given value.
+    pub fn p_value(&self, xi: f64) -> f64 {
+        // Invert xi = 1 - n*s/(2*lsum) for the rank difference sum, allowing a
+        // little slack for rounding in the caller's value of xi
+        let s = (1.-xi)*2.*self.lsum as f64/self.n as f64;
+        let s_max = (s+1e-9*s.abs().max(1.)).floor();
+
+        if s_max < 0. { return 0.; }
+
+        self.p_value_of_sum(s_max as usize)
+    }
+
+    // The probability that the sum of absolute rank differences is at most s,
+    // which is the probability that xi is at least its value for s.
+    fn p_value_of_sum(&self, s: usize) -> f64 {
+        let end = self.counts.len().min(s+1);
+        let count = self.counts[..end].iter().sum::<u64>();
+
+        count as f64/self.total as f64
+    }
+
+    fn xi(&self, s: usize) -> f64 {
+        1.-(self.n*s) as f64/(2*self.lsum) as f64
+    }
+}
+
+/// The outcome of an exact test for independence using xi.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExactTest {
+    /// The xi-correlation of the data, exactly as returned by [`xicor`].
+    ///
+    /// [`xicor`]: crate::xicor
+    pub xi: f64,
+    /// The exact probability of a xi at least this large if x and y were
+    /// independent.
+    pub p_value: f64,
+}
+
+/// Compute the exact null distribution of xi for the given y values.
+///
+/// Every distinct arrangement of the y values is enumerated, so this requires
+/// `y.len() <= MAX_EXACT_N`. The distributions for y without ties are built
+/// into the crate for every such `n`, so they cost nothing to compute. Those
+/// for y with ties are enumerated on first use and then cached for the life of
+/// the process, so the cost is only paid once for each combination of `n` and
+/// pattern of ties in y.
+///
+/// # Panics
+///
+/// If `y` has more than [`MAX_EXACT_N`] elements.
+///
+/// # Example
+///
+/// ```
+/// use xicor::null_distribution;
+///
+/// let dist = null_distribution(&[1, 2, 3]);
+///
+/// // Of the 6 orderings of 3 points, 2 give xi = 1/4 and 4 give xi = -1/8
+/// assert_eq!(dist.support(), vec![(0.25, 1./3.), (-0.125, 2./3.)]);
+/// ```
+pub fn null_distribution<T: Ord>(y: &[T]) -> Arc<NullDistribution> {
+    assert!(
+        y.len() <= MAX_EXACT_N,
+        "exact null distribution requires at most {MAX_EXACT_N} points"
+    );
+
+    let idcs = argsort(y);
+    let y_ascending = permute(y, &idcs);
+    let key = cumulative_lte(&y_ascending);
+
+    if key.iter().enumerate().all(|(i, &r)| r == i+1) {
+        let tables = TABLES.get_or_init(|| {
+            (0..=MAX_EXACT_N).map(|n| Arc::new(continuous(n))).collect()
+        });
+
+        return tables[key.len()].clone();
+    }
+
+    let cache = CACHE.get_or_init(Default::default);
+
+    if let Some(dist) = cache.lock().unwrap().get(&key) {
+        return dist.clone();
+    }
+
+    // Enumerate outside the lock, as it can take a while for larger n
+    let ls = cumulative_gte(&y_ascending);
+    let dist = Arc::new(enumerate(&key, &ls));
+
+    cache.lock().unwrap()
+        .entry(key)
+        .or_insert(dist)
+        .clone()
+}
+
+/// Test two floating-point sequences for independence using the exact null
+/// distribution of xi.
+///
+/// See [`xicor_exact_test`] for details.
+///
+/// # Example
+///
+/// ```
+/// use xicor::xicorf_exact_test;
+///
+/// let x = [0.1, 0.5, 0.2, 0.8, 0.6, 0.9];
+/// let y = [1.0, 2.5, 1.5, 4.0, 3.0, 4.5];
+/// let test = xicorf_exact_test(&x, &y);
+///
+/// // Only the 2 perfectly increasing or decreasing orderings of the 720
+/// // possible ones are as extreme as this
+/// assert_eq!(test.p_value, 2./720.);
+/// ```
+pub fn xicorf_exact_test<FX, FY>(x: &[FX], y: &[FY]) -> ExactTest
+where
+    FX: FloatCore,
+    FY: FloatCore,
+{
+    xicor_exact_test(as_ordered(x), as_ordered(y))
+}
+
+/// Test two sequences whose values are orderable (they implement [`Ord`]) for
+/// independence using the exact null distribution of xi.
+///
+/// For small samples the normal approximation used by [`xicor_test`] is poor.
+/// This function instead compares xi against its exact distribution under
+/// independence, as computed by [`null_distribution`], so the p-value is exact
+/// for any pattern of ties in y.
+///
+/// [`xicor_test`]: crate::xicor_test
+///
+/// # Panics
+///
+/// If there are more than [`MAX_EXACT_N`] points, or `x` and `y` differ in
+/// length.
+///
+/// # Example
+///
+/// ```
+/// use xicor::xicor_exact_test;
+///
+/// let x = [1, 2, 3, 4, 5, 6, 7];
+/// let y = [3, 1, 4, 1, 5, 9, 2];
+/// let test = xicor_exact_test(&x, &y);
+///
+/// // There are 7!/2! = 2520 distinct arrangements of these y values
+/// assert_eq!(test.p_value, 2152./2520.);
+/// ```
+pub fn xicor_exact_test<X: Ord, Y: Ord>(x: &[X], y: &[Y]) -> ExactTest {
+    let dist = null_distribution(y);
+    let ranks = ranks(x, y);
+    let xi = ranks.xi();
+    let p_value = dist.p_value_of_sum(rank_diff_sum(&ranks.rs) as usize);
+
+    ExactTest { xi, p_value }
+}
+
+// The precomputed null distribution for n values of y without ties.
+pub(super) fn continuous(n: usize) -> NullDistribution {
+    let counts = CONTINUOUS[n].to_vec();
+    let total = counts.iter().sum();
+
+    NullDistribution { n, lsum: (n*n*n-n)/6, counts, total }
+}
+
+// Build the null distribution by visiting every distinct permutation of the
+// ascending r_i values, each of which is equally likely under independence.
+pub(super) fn enumerate(rs_ascending: &[usize], ls: &[usize]) -> NullDistribution {
+    let n = rs_ascending.len();
+    let lsum = ls.iter().map(|l| l*(n-l)).sum();
+    let mut counts = vec![0; n*n+1];
+    let mut total = 0;
+    let mut rs = rs_ascending.to_vec();
+
+    loop {
+        let s = rs.windows(2)
+            .map(|win| win[0].abs_diff(win[1]))
+            .sum::<usize>();
+
+        counts[s] += 1;
+        total += 1;
+
+        if !next_permutation(&mut rs) { break; }
+    }
+
+    while counts.last() == Some(&0) { counts.pop(); }
+
+    NullDistribution { n, lsum, counts, total }
+}
+
+// Rearrange the array into the next lexicographically greater permutation,
+// returning false once the last permutation has been reached. Starting from
+// sorted order, this visits every distinct permutation of a multiset once.
+pub(super) fn next_permutation<T: Ord>(arr: &mut [T]) -> bool {
+    let Some(i) = arr.windows(2).rposition(|win| win[0] < win[1]) else {
+        return false;
+    };
+    let j = arr.iter().rposition(|a| *a > arr[i]).unwrap();
+
+    arr.swap(i, j);
+    arr[i+1..].reverse();
+
+    true
+}
@@ -0,0 +1,149 @@
+use crate::normal;
+use crate::xicor::{as_ordered, ranks, Ranks};
+use num_traits::float::FloatCore;
+
+
+
+/// The outcome of testing two sequences for independence using xi.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct XiTest {
+    /// The xi-correlation of the data, exactly as returned by [`xicor`].
+    ///
+    /// [`xicor`]: crate::xicor
+    pub xi: f64,
+    /// The standardised statistic `sqrt(n)*xi/sqrt(variance)`, which is
+    /// asymptotically standard normal under the null hypothesis.
+    pub z: f64,
+    /// The one-sided p-value, i.e. the probability of a z-score at least this
+    /// large if x and y were independent.
+    pub p_value: f64,
+    /// The asymptotic variance of `sqrt(n)*xi` that was used to standardise
+    /// the statistic.
+    pub null_variance: NullVariance,
+}
+
+/// The asymptotic null variance of `sqrt(n)*xi` used by an independence test.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NullVariance {
+    /// The variance 2/5, which holds when y is continuous. This is used when
+    /// there are no ties among the y values.
+    Continuous,
+    /// The general variance estimate from Chatterjee's paper, computed from the
+    /// data. This is used when the y values contain ties, for which 2/5 is no
+    /// longer correct.
+    TieCorrected(f64),
+}
+
+impl NullVariance {
+    /// The numerical value of the variance.
+    pub fn value(&self) -> f64 {
+        match *self {
+            Self::Continuous => 0.4,
+            Self::TieCorrected(var) => var,
+        }
+    }
+}
+
+/// Test two floating-point sequences for independence using xi.
+///
+/// See [`xicor_test`] for details of the test.
+///
+/// # Example
+///
+/// ```
+/// use xicor::xicorf_test;
+///
+/// let x: Vec<f64> = (0..500).map(|i| i as f64/500.).collect();
+/// let y: Vec<f64> = x.iter().map(|&x| (x*12.566).sin()).collect();
+/// let test = xicorf_test(&x, &y);
+///
+/// assert!(test.p_value < 1e-10);
+/// ```
+pub fn xicorf_test<FX: FloatCore, FY: FloatCore>(x: &[FX], y: &[FY]) -> XiTest {
+    xicor_test(as_ordered(x), as_ordered(y))
+}
+
+/// Test two sequences whose values are orderable (they implement [`Ord`]) for
+/// independence using xi.
+///
+/// Chatterjee showed that if x and y are independent and y is continuous, then
+/// `sqrt(n)*xi` converges in distribution to a normal with mean zero and
+/// variance 2/5. Since xi is only large when y depends on x, the test is
+/// one-sided: the p-value is the probability of a standard normal exceeding the
+/// standardised statistic.
+///
+/// When y contains repeated values the variance of 2/5 no longer applies, and
+/// p-values computed with it are miscalibrated. In that case the general
+/// estimator `tau^2` of the asymptotic variance from Theorem 2.2 of the paper
+/// is used instead. The variance actually used is reported in
+/// [`XiTest::null_variance`].
+///
+/// # Example
+///
+/// ```
+/// use xicor::xicor_test;
+///
+/// let x: Vec<u32> = (0..47).collect();
+/// let y: Vec<u32> = x.iter().map(|x| x*x).collect();
+/// let test = xicor_test(&x, &y);
+///
+/// assert_eq!(test.xi, 0.9375);
+/// assert!(test.p_value < 1e-10);
+/// ```
+pub fn xicor_test<X: Ord, Y: Ord>(x: &[X], y: &[Y]) -> XiTest {
+    let ranks = ranks(x, y);
+
+    asymptotic_test(ranks.xi(), ranks)
+}
+
+// Test a xi value for significance against its asymptotic null distribution.
+// The variance depends only on the multiset of r_i values, so the ranks may be
+// in any order.
+pub(super) fn asymptotic_test(xi: f64, ranks: Ranks) -> XiTest {
+    let mut us = ranks.rs;
+
+    us.sort_unstable();
+
+    let null_variance = match ranks.distinct < us.len() {
+        true => NullVariance::TieCorrected(tie_corrected_variance(&us, ranks.lsum)),
+        false => NullVariance::Continuous,
+    };
+
+    standardise(xi, us.len(), null_variance)
+}
+
+// Standardise a xi value computed from n pairs by its null variance.
+pub(super) fn standardise(xi: f64, n: usize, null_variance: NullVariance) -> XiTest {
+    let z = xi*(n as f64/null_variance.value()).sqrt();
+
+    XiTest { xi, z, p_value: normal::sf(z), null_variance }
+}
+
+// Estimate the asymptotic variance of sqrt(n)*xi under independence, allowing
+// for ties in y. This is the estimator tau_n^2 = (a - 2b + c^2)/d^2 from
+// Theorem 2.2 of the paper. The r_i must be given in ascending order, and lsum
+// is the sum of l_i(n - l_i).
+pub(super) fn tie_corrected_variance(us: &[usize], lsum: u128) -> f64 {
+    let n = us.len() as f64;
+    let mut a = 0.;
+    let mut b = 0.;
+    let mut c = 0.;
+    let mut v = 0.;
+
+    for (i, &u) in us.iter().enumerate() {
+        let i = (i+1) as f64;
+        let u = u as f64;
+
+        v += u;
+        a += (2.*n-2.*i+1.)*u*u;
+        b += (v+(n-i)*u).powi(2);
+        c += (2.*n-2.*i+1.)*u;
+    }
+
+    let a = a/n.powi(4);
+    let b = b/n.powi(5);
+    let c = c/n.powi(3);
+    let d = lsum as f64/n.powi(3);
+
+    (a-2.*b+c*c)/(d*d)
+}
@@ -0,0 +1,269 @@
+use crate::correlation::XiCorrelation;
+use crate::error::XiError;
+use crate::xicor::{check_lengths, ranks, Ranks};
+use num_traits::float::FloatCore;
+use ordered_float::OrderedFloat;
+
+
+
+/// How NaN values in floating-point data are handled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NanPolicy {
+    /// Fail with [`XiError::NaN`] if any value is NaN.
+    #[default]
+    Error,
+    /// Return NaN as the coefficient if any value is NaN.
+    Propagate,
+    /// Drop every pair in which either value is NaN, and compute xi from the
+    /// remaining complete pairs.
+    Drop,
+    /// Treat NaN as a distinct value smaller than every number. All NaNs are
+    /// tied with each other.
+    Lowest,
+    /// Treat NaN as a distinct value larger than every number. All NaNs are
+    /// tied with each other. This is what [`xicorf`] does implicitly.
+    ///
+    /// [`xicorf`]: crate::xicorf
+    Highest,
+}
+
+/// The xi-correlation of the complete pairs in a dataset with missing values.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PairwiseXi {
+    /// The xi-correlation (or normalised xi-correlation) of the pairs used.
+    pub xi: f64,
+    /// The effective sample size, which is the number of pairs used.
+    pub n: usize,
+    /// The number of pairs dropped because a value was missing.
+    pub dropped: usize,
+}
+
+/// Calculate the normalised xi-correlation of two floating-point sequences,
+/// handling NaN values according to the given policy.
+///
+/// This is the counterpart of [`xicorf_norm`] to [`xicorf_with_nan`]. When
+/// pairs are dropped, the normalisation uses the number of pairs remaining.
+/// At least 3 pairs are required.
+///
+/// [`xicorf_norm`]: crate::xicorf_norm
+///
+/// # Example
+///
+/// ```
+/// use xicor::{xicorf_norm_with_nan, NanPolicy};
+///
+/// let x: Vec<f64> = (0..48).map(|i| i as f64).collect();
+/// let mut y: Vec<f64> = x.iter().map(|x| x*x).collect();
+///
+/// y[20] = f64::NAN;
+///
+/// let result = xicorf_norm_with_nan(&x, &y, NanPolicy::Drop).unwrap();
+///
+/// assert_eq!(result.xi, 1.);
+/// assert_eq!(result.n, 47);
+/// ```
+pub fn xicorf_norm_with_nan<FX, FY>(
+    x: &[FX],
+    y: &[FY],
+    nan: NanPolicy,
+) -> Result<PairwiseXi, XiError>
+where
+    FX: FloatCore,
+    FY: FloatCore,
+{
+    let result = xicorf_with_nan(x, y, nan)?;
+
+    normalise(result)
+}
+
+/// Calculate the xi-correlation of two floating-point sequences, handling NaN
+/// values according to the given policy.
+///
+/// [`xicorf`] silently sorts NaN above every number, so a single missing
+/// reading shifts the ranks of every other value. This function makes the
+/// choice explicit, and reports how many pairs were dropped under
+/// [`NanPolicy::Drop`]. Like [`try_xicorf`], it returns an error rather than
+/// panicking or producing NaN for unsuitable data, except that
+/// [`NanPolicy::Propagate`] deliberately produces NaN.
+///
+/// [`xicorf`]: crate::xicorf
+/// [`try_xicorf`]: crate::try_xicorf
+///
+/// # Example
+///
+/// ```
+/// use xicor::{xicorf, xicorf_with_nan, NanPolicy, XiError};
+///
+/// let x = [0.1, 0.5, 0.2, 0.8, f64::NAN, 0.9];
+/// let y = [1.0, 2.5, 1.5, 4.0, 3.0, 4.5];
+///
+/// assert_eq!(xicorf_with_nan(&x, &y, NanPolicy::Error), Err(XiError::NaN));
+/// assert!(xicorf_with_nan(&x, &y, NanPolicy::Propagate).unwrap().xi.is_nan());
+///
+/// let result = xicorf_with_nan(&x, &y, NanPolicy::Drop).unwrap();
+///
+/// let complete = xicorf(&[0.1, 0.5, 0.2, 0.8, 0.9], &[1.0, 2.5, 1.5, 4.0, 4.5]);
+///
+/// assert_eq!(result.dropped, 1);
+/// assert_eq!(result.xi, complete);
+/// ```
+pub fn xicorf_with_nan<FX, FY>(
+    x: &[FX],
+    y: &[FY],
+    nan: NanPolicy,
+) -> Result<PairwiseXi, XiError>
+where
+    FX: FloatCore,
+    FY: FloatCore,
+{
+    let result = XiCorrelation::new().nan(nan).computef(x, y)?;
+
+    Ok(PairwiseXi { xi: result.xi, n: result.n, dropped: result.dropped })
+}
+
+/// Calculate the normalised xi-correlation of the complete pairs of two
+/// floating-point sequences with missing values.
+///
+/// See [`xicorf_pairwise`] for details. The normalisation uses the effective
+/// sample size, so at least 3 complete pairs are required.
+///
+/// # Example
+///
+/// ```
+/// use xicor::xicorf_norm_pairwise;
+///
+/// let x = [Some(0.), Some(1.), None, Some(3.), Some(4.)];
+/// let y = [Some(0.), Some(1.), Some(4.), Some(9.), None];
+/// let result = xicorf_norm_pairwise(&x, &y).unwrap();
+///
+/// assert_eq!(result.xi, 1.);
+/// assert_eq!(result.n, 3);
+/// ```
+pub fn xicorf_norm_pairwise<FX, FY>(
+    x: &[Option<FX>],
+    y: &[Option<FY>],
+) -> Result<PairwiseXi, XiError>
+where
+    FX: FloatCore,
+    FY: FloatCore,
+{
+    normalise(xicorf_pairwise(x, y)?)
+}
+
+/// Calculate the normalised xi-correlation of the complete pairs of two
+/// sequences with missing values.
+///
+/// See [`xicor_pairwise`] for details. The normalisation uses the effective
+/// sample size, so at least 3 complete pairs are required.
+///
+/// # Example
+///
+/// ```
+/// use xicor::xicor_norm_pairwise;
+///
+/// let x = [Some(0), Some(1), None, Some(3), Some(4)];
+/// let y = [Some(0), Some(1), Some(4), Some(9), None];
+/// let result = xicor_norm_pairwise(&x, &y).unwrap();
+///
+/// assert_eq!(result.xi, 1.);
+/// assert_eq!(result.dropped, 2);
+/// ```
+pub fn xicor_norm_pairwise<X: Ord, Y: Ord>(
+    x: &[Option<X>],
+    y: &[Option<Y>],
+) -> Result<PairwiseXi, XiError> {
+    normalise(xicor_pairwise(x, y)?)
+}
+
+/// Calculate the xi-correlation of the complete pairs of two floating-point
+/// sequences with missing values.
+///
+/// This is the float counterpart of [`xicor_pairwise`]. Missing values are
+/// marked with `None`, and a NaN inside `Some` is still an error, since it is
+/// usually the result of a bad calculation rather than a gap in the data. Use
+/// [`xicorf_with_nan`] if NaN itself marks the gaps.
+///
+/// # Example
+///
+/// ```
+/// use xicor::{xicorf, xicorf_pairwise};
+///
+/// let x = [Some(0.1), Some(0.5), None, Some(0.8), Some(0.9)];
+/// let y = [Some(1.0), Some(2.5), Some(1.5), Some(4.0), Some(4.5)];
+/// let result = xicorf_pairwise(&x, &y).unwrap();
+///
+/// assert_eq!(result.xi, xicorf(&[0.1, 0.5, 0.8, 0.9], &[1.0, 2.5, 4.0, 4.5]));
+/// ```
+pub fn xicorf_pairwise<FX, FY>(
+    x: &[Option<FX>],
+    y: &[Option<FY>],
+) -> Result<PairwiseXi, XiError>
+where
+    FX: FloatCore,
+    FY: FloatCore,
+{
+    let x_nan = x.iter().any(|v| v.is_some_and(|v| v.is_nan()));
+    let y_nan = y.iter().any(|v| v.is_some_and(|v| v.is_nan()));
+
+    if x_nan || y_nan { return Err(XiError::NaN); }
+
+    let x: Vec<_> = x.iter().map(|v| v.map(OrderedFloat)).collect();
+    let y: Vec<_> = y.iter().map(|v| v.map(OrderedFloat)).collect();
+
+    xicor_pairwise(&x, &y)
+}
+
+/// Calculate the xi-correlation of the complete pairs of two sequences with
+/// missing values, whose values are orderable (they implement [`Ord`]).
+///
+/// Any pair in which either value is `None` is dropped (pairwise deletion),
+/// and xi is computed from the remaining pairs as if they were the whole
+/// dataset. The result records the effective sample size, which should be
+/// used in place of the original length for anything that depends on n, such
+/// as the asymptotic variance of xi.
+///
+/// # Example
+///
+/// ```
+/// use xicor::{xicor, xicor_pairwise};
+///
+/// let x = [Some(1), Some(2), None, Some(4), Some(5), Some(6)];
+/// let y = [Some(3), None, Some(2), Some(1), Some(5), Some(4)];
+/// let result = xicor_pairwise(&x, &y).unwrap();
+///
+/// assert_eq!(result.xi, xicor(&[1, 4, 5, 6], &[3, 1, 5, 4]));
+/// assert_eq!(result.n, 4);
+/// assert_eq!(result.dropped, 2);
+/// ```
+pub fn xicor_pairwise<X: Ord, Y: Ord>(
+    x: &[Option<X>],
+    y: &[Option<Y>],
+) -> Result<PairwiseXi, XiError> {
+    check_lengths(x, y)?;
+
+    let n = x.len();
+    let (x, y): (Vec<&X>, Vec<&Y>) = x.iter()
+        .zip(y)
+        .filter_map(|(x, y)| Some((x.as_ref()?, y.as_ref()?)))
+        .unzip();
+
+    pairwise(ranks(&x, &y), n)
+}
+
+// Calculate xi from the ranks of the pairs used, out of n pairs in total.
+pub(super) fn pairwise(ranks: Ranks, n: usize) -> Result<PairwiseXi, XiError> {
+    let xi = ranks.try_xi()?;
+    let used = ranks.rs.len();
+
+    Ok(PairwiseXi { xi, n: used, dropped: n-used })
+}
+
+// Divide xi by its maximum value (n-2)/(n+1) for the effective sample size.
+pub(super) fn normalise(result: PairwiseXi) -> Result<PairwiseXi, XiError> {
+    if result.n < 3 { return Err(XiError::TooFewSamples { n: result.n }); }
+
+    let n = result.n as f64;
+    let lim = (n-2.)/(n+1.);
+
+    Ok(PairwiseXi { xi: result.xi/lim, ..result })
+}
@@ -0,0 +1,127 @@
+use std::f64::consts::{FRAC_1_SQRT_2, PI};
+
+
+
+// Survival function of the standard normal distribution, i.e. the probability
+// that a standard normal variable exceeds z.
+pub(super) fn sf(z: f64) -> f64 {
+    0.5*erfc(z*FRAC_1_SQRT_2)
+}
+
+// Cumulative distribution function of the standard normal distribution.
+pub(super) fn cdf(z: f64) -> f64 {
+    sf(-z)
+}
+
+// Quantile function (inverse CDF) of the standard normal distribution. This
+// uses Acklam's rational approximation, followed by one step of Halley's method
+// to bring the relative error down to near machine precision.
+pub(super) fn quantile(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969683028665376e+01, 2.209460984245205e+02, -2.759285104469687e+02,
+        1.38357751867269e+02, -3.066479806614716e+01, 2.506628277459239e+00,
+    ];
+    const B: [f64; 5] = [
+        -5.447609879822406e+01, 1.615858368580409e+02, -1.556989798598866e+02,
+        6.680131188771972e+01, -1.328068155288572e+01,
+    ];
+    const C: [f64; 6] = [
+        -7.784894002430293e-03, -3.223964580411365e-01, -2.400758277161838e+00,
+        -2.549732539343734e+00, 4.374664141464968e+00, 2.938163982698783e+00,
+    ];
+    const D: [f64; 4] = [
+        7.784695709041462e-03, 3.224671290700398e-01, 2.445134137142996e+00,
+        3.754408661907416e+00,
+    ];
+    const P_LOW: f64 = 0.02425;
+
+    if p.is_nan() || !(0. ..=1.).contains(&p) { return f64::NAN; }
+    if p == 0. { return f64::NEG_INFINITY; }
+    if p == 1. { return f64::INFINITY; }
+
+    let tail = |q: f64| {
+        let num = ((((C[0]*q+C[1])*q+C[2])*q+C[3])*q+C[4])*q+C[5];
+        let den = (((D[0]*q+D[1])*q+D[2])*q+D[3])*q+1.;
+
+        num/den
+    };
+
+    let x = if p < P_LOW {
+        tail((-2.*p.ln()).sqrt())
+    } else if p > 1.-P_LOW {
+        -tail((-2.*(1.-p).ln()).sqrt())
+    } else {
+        let q = p-0.5;
+        let r = q*q;
+        let num = (((((A[0]*r+A[1])*r+A[2])*r+A[3])*r+A[4])*r+A[5])*q;
+        let den = ((((B[0]*r+B[1])*r+B[2])*r+B[3])*r+B[4])*r+1.;
+
+        num/den
+    };
+
+    let e = cdf(x)-p;
+    let u = e*(2.*PI).sqrt()*(x*x/2.).exp();
+
+    x-u/(1.+x*u/2.)
+}
+
+// Complementary error function, accurate to near machine precision. A Taylor
+// series is used for small arguments and a continued fraction for large ones,
+// so that the tail probabilities keep their relative accuracy.
+fn erfc(x: f64) -> f64 {
+    if x.is_nan() { return f64::NAN; }
+    if x < 0. { return 2.-erfc(-x); }
+    if x < 2. { return 1.-erf_series(x); }
+    if x > 27. { return 0.; }
+
+    erfc_continued_fraction(x)
+}
+
+// Maclaurin series erf(x) = 2/sqrt(pi) * sum (-1)^k x^(2k+1) / (k! (2k+1)).
+fn erf_series(x: f64) -> f64 {
+    let x2 = x*x;
+    let mut term = x;
+    let mut sum = x;
+
+    for k in 1..100 {
+        term *= -x2/k as f64;
+
+        let contribution = term/(2*k+1) as f64;
+
+        sum += contribution;
+
+        if contribution.abs() < 1e-17*sum.abs() { break; }
+    }
+
+    2./PI.sqrt()*sum
+}
+
+// Continued fraction erfc(x) = exp(-x^2)/sqrt(pi) * 1/(x + (1/2)/(x + 1/(x +
+// (3/2)/(x + ...)))), evaluated with the modified Lentz algorithm.
+fn erfc_continued_fraction(x: f64) -> f64 {
+    const TINY: f64 = 1e-300;
+
+    let mut f = x;
+    let mut c = x;
+    let mut d = 0.;
+
+    for k in 1..500 {
+        let a = k as f64/2.;
+
+        d = x+a*d;
+        c = x+a/c;
+
+        if d.abs() < TINY { d = TINY; }
+        if c.abs() < TINY { c = TINY; }
+
+        d = 1./d;
+
+        let delta = c*d;
+
+        f *= delta;
+
+        if (delta-1.).abs() < 1e-16 { break; }
+    }
+
+    (-x*x).exp()/(PI.sqrt()*f)
+}
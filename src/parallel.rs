@@ -0,0 +1,110 @@
+use crate::radix::radix_keys;
+use crate::xicor::{as_ordered, XiRatio};
+use num_traits::float::FloatCore;
+use rayon::prelude::*;
+
+
+
+/// Calculate the xi-correlation of two floating-point sequences in parallel.
+///
+/// See [`xicor_par`] for details.
+///
+/// # Example
+///
+/// ```
+/// use xicor::{xicorf, xicorf_par};
+///
+/// let x: Vec<f64> = (0..10000).map(|i| i as f64/10000.).collect();
+/// let y: Vec<f64> = x.iter().map(|&x| (x*12.566).sin()).collect();
+///
+/// assert_eq!(xicorf_par(&x, &y), xicorf(&x, &y));
+/// ```
+pub fn xicorf_par<FX, FY>(x: &[FX], y: &[FY]) -> f64
+where
+    FX: FloatCore + Sync,
+    FY: FloatCore + Sync,
+{
+    xicor_par(as_ordered(x), as_ordered(y))
+}
+
+/// Calculate the xi-correlation of two sequences whose values are orderable
+/// (they implement [`Ord`]) in parallel, using rayon.
+///
+/// Both sorts, the rearrangement of y into x order, the rank computation and
+/// the final sums are all spread across rayon's thread pool. The sums are
+/// accumulated as integers, so the result is bit-identical to [`xicor`]
+/// regardless of how the work is split between threads.
+///
+/// This function is only available with the `rayon` feature.
+///
+/// [`xicor`]: crate::xicor
+///
+/// # Panics
+///
+/// If `x` and `y` differ in length.
+///
+/// # Example
+///
+/// ```
+/// use xicor::{xicor, xicor_par};
+///
+/// let x: Vec<u32> = (0..10000).collect();
+/// let y: Vec<u32> = x.iter().map(|x| (x*x)%1009).collect();
+///
+/// assert_eq!(xicor_par(&x, &y), xicor(&x, &y));
+/// ```
+pub fn xicor_par<X, Y>(x: &[X], y: &[Y]) -> f64
+where
+    X: Ord + Sync,
+    Y: Ord + Sync,
+{
+    assert!(x.len() == y.len(), "x and y must have the same length");
+
+    let n = x.len();
+    let x_idcs = par_argsort(x);
+    let y_ord: Vec<&Y> = x_idcs.par_iter().map(|&i| &y[i]).collect();
+    let y_ascending: Vec<&Y> = par_argsort(&y_ord).into_par_iter()
+        .map(|i| y_ord[i])
+        .collect();
+
+    // Rather than scattering the ranks from y order back into x order, which
+    // cannot be split between threads, each point finds its ranks by binary
+    // searching the sorted y values
+    let rs: Vec<usize> = y_ord.par_iter()
+        .map(|&v| y_ascending.partition_point(|&u| u <= v))
+        .collect();
+    let lsum = y_ord.par_iter()
+        .map(|&v| n-y_ascending.partition_point(|&u| u < v))
+        .map(|l| l as u128*(n-l) as u128)
+        .sum::<u128>();
+    let rsum = rs.par_windows(2)
+        .map(|win| win[0].abs_diff(win[1]))
+        .map(|d| d as u128)
+        .sum::<u128>();
+
+    XiRatio::new(n, rsum, lsum).to_f64()
+}
+
+// Return the indices that would sort the given array, in parallel. Equal
+// elements keep their relative order from the input, exactly as for argsort.
+fn par_argsort<T: Ord + Sync>(arr: &[T]) -> Vec<usize> {
+    // Pairing keys with indices makes every element distinct, so the unstable
+    // sort still gives a deterministic order
+    let mut keys = vec![];
+
+    if radix_keys(arr, &mut keys).is_some() {
+        let mut pairs: Vec<(u64, usize)> = keys.into_par_iter()
+            .enumerate()
+            .map(|(i, key)| (key, i))
+            .collect();
+
+        pairs.par_sort_unstable();
+
+        return pairs.into_par_iter().map(|(_, i)| i).collect();
+    }
+
+    let mut idcs: Vec<usize> = (0..arr.len()).collect();
+
+    idcs.par_sort_unstable_by(|&i, &j| arr[i].cmp(&arr[j]).then(i.cmp(&j)));
+    idcs
+}
@@ -0,0 +1,165 @@
+use crate::xicor::{as_ordered, rank_diff_sum, ranks};
+use num_traits::float::FloatCore;
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+
+
+// The minimum number of permutations drawn before early stopping is considered,
+// so that the standard error estimate is not dominated by noise.
+const EARLY_STOP_MIN: usize = 100;
+
+// How many standard errors the p-value must lie from the threshold before the
+// test is stopped early.
+const EARLY_STOP_Z: f64 = 3.;
+
+/// Settings controlling how many permutations a permutation test draws.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PermutationOptions {
+    /// The maximum number of permutations to draw.
+    pub permutations: usize,
+    /// If set, stop drawing permutations once the p-value is clearly (by more
+    /// than 3 standard errors) above or below this significance level.
+    pub early_stop: Option<f64>,
+}
+
+impl PermutationOptions {
+    /// Draw exactly the given number of permutations.
+    pub fn new(permutations: usize) -> Self {
+        Self { permutations, early_stop: None }
+    }
+
+    /// Stop early once the p-value is clearly above or below `alpha`.
+    pub fn early_stop(self, alpha: f64) -> Self {
+        Self { early_stop: Some(alpha), ..self }
+    }
+}
+
+/// The outcome of a permutation test for independence using xi.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PermutationTest {
+    /// The xi-correlation of the data, exactly as returned by [`xicor`].
+    ///
+    /// [`xicor`]: crate::xicor
+    pub xi: f64,
+    /// The Monte Carlo p-value `(1+k)/(1+B)`, where `k` of the `B` permuted
+    /// datasets had a xi at least as large as the observed one.
+    pub p_value: f64,
+    /// The Monte Carlo standard error of the p-value.
+    pub std_error: f64,
+    /// The number of permutations actually drawn, which is less than the
+    /// maximum if the test stopped early.
+    pub permutations: usize,
+}
+
+/// Test two floating-point sequences for independence by permuting y.
+///
+/// See [`xicor_permutation_test`] for details of the test.
+///
+/// # Example
+///
+/// ```
+/// use rand::SeedableRng;
+/// use rand::rngs::StdRng;
+/// use xicor::{xicorf_permutation_test, PermutationOptions};
+///
+/// let x: Vec<f64> = (0..50).map(|i| i as f64/50.).collect();
+/// let y: Vec<f64> = x.iter().map(|&x| (x*12.566).sin()).collect();
+/// let mut rng = StdRng::seed_from_u64(1);
+/// let test = xicorf_permutation_test(&x, &y, PermutationOptions::new(999), &mut rng);
+///
+/// assert_eq!(test.p_value, 0.001);
+/// ```
+pub fn xicorf_permutation_test<FX, FY, R>(
+    x: &[FX],
+    y: &[FY],
+    options: PermutationOptions,
+    rng: &mut R,
+) -> PermutationTest
+where
+    FX: FloatCore,
+    FY: FloatCore,
+    R: Rng + ?Sized,
+{
+    xicor_permutation_test(as_ordered(x), as_ordered(y), options, rng)
+}
+
+/// Test two sequences whose values are orderable (they implement [`Ord`]) for
+/// independence by permuting y.
+///
+/// The y values are shuffled relative to the x values, and xi is recomputed
+/// for each shuffle. Under the null hypothesis of independence every shuffle is
+/// equally likely, so the fraction of shuffles with a xi at least as large as
+/// the observed one is a valid p-value for any sample size and any pattern of
+/// ties. The randomness comes entirely from `rng`, so seeding it makes the test
+/// reproducible.
+///
+/// The ordering of x and the ranks of y are computed only once, since shuffling
+/// y merely rearranges its ranks. Each permutation therefore costs `O(n)`.
+///
+/// # Example
+///
+/// ```
+/// use rand::SeedableRng;
+/// use rand::rngs::StdRng;
+/// use xicor::{xicor_permutation_test, PermutationOptions};
+///
+/// let x: Vec<u32> = (0..100).collect();
+/// let y: Vec<u32> = x.iter().map(|x| (x*x)%17).collect();
+/// let options = PermutationOptions::new(10000).early_stop(0.05);
+/// let mut rng = StdRng::seed_from_u64(1);
+/// let test = xicor_permutation_test(&x, &y, options, &mut rng);
+///
+/// // The dependence is so clear that far fewer permutations were needed
+/// assert!(test.p_value < 0.05);
+/// assert!(test.permutations < 10000);
+/// ```
+pub fn xicor_permutation_test<X, Y, R>(
+    x: &[X],
+    y: &[Y],
+    options: PermutationOptions,
+    rng: &mut R,
+) -> PermutationTest
+where
+    X: Ord,
+    Y: Ord,
+    R: Rng + ?Sized,
+{
+    let ranks = ranks(x, y);
+    let xi = ranks.xi();
+    let mut rs = ranks.rs;
+
+    // The denominator of xi does not change under permutation, so comparing the
+    // numerators is equivalent to comparing the xi values themselves
+    let rsum = rank_diff_sum(&rs);
+    let mut exceedances = 0;
+    let mut permutations = 0;
+
+    while permutations < options.permutations {
+        rs.shuffle(rng);
+        permutations += 1;
+
+        if rank_diff_sum(&rs) <= rsum { exceedances += 1; }
+
+        if let Some(alpha) = options.early_stop {
+            let (p, se) = monte_carlo_p_value(exceedances, permutations);
+
+            if permutations >= EARLY_STOP_MIN && (p-alpha).abs() > EARLY_STOP_Z*se {
+                break;
+            }
+        }
+    }
+
+    let (p_value, std_error) = monte_carlo_p_value(exceedances, permutations);
+
+    PermutationTest { xi, p_value, std_error, permutations }
+}
+
+// Return the Monte Carlo p-value and its standard error, given that k out of b
+// permutations produced a statistic at least as extreme as the observed one.
+fn monte_carlo_p_value(k: usize, b: usize) -> (f64, f64) {
+    let p = (1+k) as f64/(1+b) as f64;
+    let se = (p*(1.-p)/b as f64).sqrt();
+
+    (p, se)
+}
@@ -0,0 +1,366 @@
+use ordered_float::OrderedFloat;
+use std::ops::Range;
+
+
+
+// The lengths for which a radix sort of words with 32-bit keys beats the
+// standard library's unstable sort. Below this range the radix passes cost
+// more than they save, and above it scattering into 256 buckets at once
+// thrashes the cache. Measured on random keys, the radix sort is up to twice
+// as fast within the range and half again as slow beyond it. Words with
+// 64-bit keys need twice the passes over twice the memory, and were never
+// sorted faster by radix, so they are always sorted by comparison.
+const RADIX_LENS: Range<usize> = 1 << 11..1 << 19;
+
+// Buffers for sorting by radix keys, which XiWorkspace keeps and reuses
+// between calls to avoid allocating. Each buffer only grows once an input
+// needs it.
+#[derive(Clone, Debug, Default)]
+pub(super) struct RadixBuffers {
+    keys_x: Vec<u64>,
+    keys_y: Vec<u64>,
+    narrow_buf: Vec<u64>,
+    wide: Vec<u128>,
+    // Whether the keys last ranked were sorted in narrow and wide words, or
+    // None if the last input had no radix keys
+    used: Option<(bool, bool)>,
+}
+
+impl RadixBuffers {
+    pub fn with_capacity(n: usize) -> Self {
+        Self {
+            keys_x: Vec::with_capacity(n),
+            keys_y: Vec::with_capacity(n),
+            narrow_buf: Vec::with_capacity(n),
+            wide: Vec::with_capacity(n),
+            used: None,
+        }
+    }
+
+    // The largest number of elements with the types last ranked that can be
+    // ranked again without allocating, or None if they had no radix keys.
+    pub fn capacity(&self) -> Option<usize> {
+        let (narrow, wide) = self.used?;
+        let mut capacity = self.keys_x.capacity().min(self.keys_y.capacity());
+
+        // Narrow words only need the buffer for lengths in RADIX_LENS
+        if narrow {
+            let buf = self.narrow_buf.capacity();
+
+            capacity = capacity.min(match buf >= RADIX_LENS.end-1 {
+                true => usize::MAX,
+                false => buf.max(RADIX_LENS.start-1),
+            });
+        }
+
+        if wide { capacity = capacity.min(self.wide.capacity()); }
+
+        Some(capacity)
+    }
+}
+
+// A sort key packed above the index of its element, so that sorting the words
+// orders the indices by key, with equal keys in order of index.
+trait Packed: Copy + Ord {
+    fn pack(key: u64, idx: usize) -> Self;
+    fn idx(self) -> usize;
+    fn key(self) -> u64;
+}
+
+// 32-bit keys with 32-bit indices.
+impl Packed for u64 {
+    fn pack(key: u64, idx: usize) -> Self {
+        key << 32 | idx as u64
+    }
+
+    fn idx(self) -> usize {
+        self as u32 as usize
+    }
+
+    fn key(self) -> u64 {
+        self >> 32
+    }
+}
+
+// 64-bit keys with 64-bit indices.
+impl Packed for u128 {
+    fn pack(key: u64, idx: usize) -> Self {
+        (key as u128) << 64 | idx as u128
+    }
+
+    fn idx(self) -> usize {
+        self as u64 as usize
+    }
+
+    fn key(self) -> u64 {
+        (self >> 64) as u64
+    }
+}
+
+// Return the indices that would sort the given array by sorting radix keys,
+// if the element type is a primitive integer or an OrderedFloat (or a
+// reference to one), and None otherwise. Like argsort, equal elements keep
+// their relative order from the input.
+pub(super) fn radix_argsort<T>(arr: &[T]) -> Option<Vec<usize>> {
+    let mut keys = vec![];
+    let bits = radix_keys(arr, &mut keys)?;
+
+    // Packing in place means the only allocation is the one returned
+    if fits_narrow(bits, arr.len()) {
+        pack_in_place(&mut keys);
+        sort_narrow(&mut keys, &mut vec![]);
+
+        return Some(keys.into_iter().map(u64::idx).collect());
+    }
+
+    let mut words: Vec<u128> = pack(&keys).collect();
+
+    drop(keys);
+    words.sort_unstable();
+
+    Some(words.into_iter().map(u128::idx).collect())
+}
+
+// Compute the rank quantities of xicor by sorting radix keys, if both element
+// types allow it. The r_i values are written to rs in ascending order of x,
+// and the sum of l_i(n - l_i) and the number of distinct y values are
+// returned.
+//
+// x is sorted first, then its keys are overwritten with the keys of y in x
+// order, so that sorting those gives the ranks of y directly in x order.
+pub(super) fn radix_ranks<X, Y>(
+    x: &[X],
+    y: &[Y],
+    bufs: &mut RadixBuffers,
+    rs: &mut Vec<usize>,
+) -> Option<(u128, usize)> {
+    let n = x.len();
+
+    bufs.used = None;
+
+    let bits_y = radix_keys(y, &mut bufs.keys_y)?;
+    let bits_x = radix_keys(x, &mut bufs.keys_x)?;
+    let RadixBuffers { keys_x, keys_y, narrow_buf, wide, used } = bufs;
+    let (narrow_x, narrow_y) = (fits_narrow(bits_x, n), fits_narrow(bits_y, n));
+    let (narrow, wide_used) = (narrow_x || narrow_y, !narrow_x || !narrow_y);
+
+    // Only the buffers that these keys are sorted in are grown
+    if narrow && RADIX_LENS.contains(&n) {
+        narrow_buf.clear();
+        narrow_buf.reserve(n);
+    }
+
+    if wide_used {
+        wide.clear();
+        wide.reserve(n);
+    }
+
+    *used = Some((narrow, wide_used));
+
+    if narrow_x {
+        pack_in_place(keys_x);
+        sort_narrow(keys_x, narrow_buf);
+
+        for word in keys_x.iter_mut() { *word = keys_y[word.idx()]; }
+    } else {
+        wide.clear();
+        wide.extend(pack(keys_x));
+        wide.sort_unstable();
+
+        for (key, word) in keys_x.iter_mut().zip(wide.iter()) {
+            *key = keys_y[word.idx()];
+        }
+    }
+
+    rs.resize(n, 0);
+
+    if narrow_y {
+        pack_in_place(keys_x);
+        sort_narrow(keys_x, narrow_buf);
+
+        Some(scan_runs(keys_x, rs))
+    } else {
+        wide.clear();
+        wide.extend(pack(keys_x));
+        wide.sort_unstable();
+
+        Some(scan_runs(wide, rs))
+    }
+}
+
+// Whether keys of the given number of bits for n elements fit in u64 words.
+fn fits_narrow(bits: u32, n: usize) -> bool {
+    bits <= 32 && n as u64 <= 1 << 32
+}
+
+fn pack_in_place(keys: &mut [u64]) {
+    for (i, key) in keys.iter_mut().enumerate() { *key = u64::pack(*key, i); }
+}
+
+fn pack(keys: &[u64]) -> impl Iterator<Item = u128> + '_ {
+    keys.iter().enumerate().map(|(i, &key)| u128::pack(key, i))
+}
+
+// Set r for every element from sorted words, returning the sum of
+// l_i(n - l_i) and the number of runs of equal keys. Every value in a run
+// spanning sorted positions start..end has r = end and l = n - start.
+fn scan_runs<W: Packed>(words: &[W], rs: &mut [usize]) -> (u128, usize) {
+    let n = words.len();
+    let mut lsum = 0;
+    let mut distinct = 0;
+    let mut start = 0;
+
+    for end in 1..=n {
+        if end < n && words[end-1].key() == words[end].key() { continue; }
+
+        let l = (n-start) as u128;
+
+        for word in &words[start..end] { rs[word.idx()] = end; }
+
+        lsum += (end-start) as u128*l*(n as u128-l);
+        distinct += 1;
+        start = end;
+    }
+
+    (lsum, distinct)
+}
+
+// Sort words with 32-bit keys, which must be in ascending order of index.
+// Within the range where it is faster, this is a stable LSD radix sort over
+// the key bytes, taking one byte per pass. Every byte is counted in a single
+// read of the words, and passes in which every key has the same byte are
+// skipped, so small keys only pay for the bytes they use.
+fn sort_narrow(words: &mut [u64], buf: &mut Vec<u64>) {
+    let n = words.len();
+
+    if !RADIX_LENS.contains(&n) {
+        words.sort_unstable();
+        return;
+    }
+
+    let digit = |word: u64, b: usize| (word >> (32+8*b)) as usize & 0xff;
+    let mut counts = [[0; 256]; 4];
+
+    for &word in words.iter() {
+        for (b, counts) in counts.iter_mut().enumerate() {
+            counts[digit(word, b)] += 1;
+        }
+    }
+
+    buf.clear();
+    buf.resize(n, 0);
+
+    let first = words[0];
+    let mut src = words;
+    let mut dst = &mut buf[..];
+    let mut swapped = false;
+
+    for (b, counts) in counts.iter_mut().enumerate() {
+        if counts[digit(first, b)] == n { continue; }
+
+        let mut offset = 0;
+
+        for count in counts.iter_mut() {
+            (*count, offset) = (offset, offset+*count);
+        }
+
+        for &word in src.iter() {
+            let d = digit(word, b);
+
+            dst[counts[d]] = word;
+            counts[d] += 1;
+        }
+
+        (src, dst) = (dst, src);
+        swapped = !swapped;
+    }
+
+    // After an odd number of passes the result is in the buffer
+    if swapped { dst.copy_from_slice(src); }
+}
+
+// Write a key for every element into keys, whose unsigned order matches the
+// element order, and return the number of bits the keys use, if the element
+// type is one that this is possible for.
+//
+// There is no specialisation in stable Rust, so the element type is identified
+// at runtime. typeid compares types with their lifetimes erased, which allows
+// references to be recognised too, since the ranking code sorts those.
+pub(super) fn radix_keys<T>(arr: &[T], keys: &mut Vec<u64>) -> Option<u32> {
+    macro_rules! keys_if {
+        ($t:ty, $bits:expr, $key:expr) => {
+            if typeid::of::<T>() == typeid::of::<$t>() {
+                // This is safe because T is $t, up to lifetimes
+                let arr = unsafe { &*(arr as *const [T] as *const [$t]) };
+
+                keys.clear();
+                keys.extend(arr.iter().map(|&v| $key(v)));
+
+                return Some($bits);
+            }
+
+            if typeid::of::<T>() == typeid::of::<&$t>() {
+                let arr = unsafe { &*(arr as *const [T] as *const [&$t]) };
+
+                keys.clear();
+                keys.extend(arr.iter().map(|&&v| $key(v)));
+
+                return Some($bits);
+            }
+        };
+    }
+
+    keys_if!(u8, 32, |v: u8| v as u64);
+    keys_if!(u16, 32, |v: u16| v as u64);
+    keys_if!(u32, 32, |v: u32| v as u64);
+    keys_if!(u64, 64, |v: u64| v);
+    keys_if!(usize, usize::BITS, |v: usize| v as u64);
+    keys_if!(i8, 32, |v: i8| signed_key32(v as i32));
+    keys_if!(i16, 32, |v: i16| signed_key32(v as i32));
+    keys_if!(i32, 32, signed_key32);
+    keys_if!(i64, 64, signed_key);
+    keys_if!(isize, usize::BITS, |v: isize| match usize::BITS {
+        32 => signed_key32(v as i32),
+        _ => signed_key(v as i64),
+    });
+    keys_if!(OrderedFloat<f32>, 32, |v: OrderedFloat<f32>| float_key32(v.0));
+    keys_if!(OrderedFloat<f64>, 64, |v: OrderedFloat<f64>| float_key(v.0));
+
+    None
+}
+
+// Flipping the sign bit of a two's complement integer makes unsigned order
+// match signed order.
+fn signed_key(v: i64) -> u64 {
+    v as u64^(1 << 63)
+}
+
+fn signed_key32(v: i32) -> u64 {
+    (v as u32^(1 << 31)) as u64
+}
+
+// Positive floats order correctly as unsigned integers once the sign bit is
+// set, and negative floats once all their bits are flipped. OrderedFloat also
+// considers -0 equal to +0 and every NaN equal to every other, above infinity,
+// so those are canonicalised first.
+fn float_key(v: f64) -> u64 {
+    if v.is_nan() { return u64::MAX; }
+
+    let bits = if v == 0. { 0 } else { v.to_bits() };
+
+    match bits >> 63 {
+        1 => !bits,
+        _ => bits | (1 << 63),
+    }
+}
+
+fn float_key32(v: f32) -> u64 {
+    if v.is_nan() { return u32::MAX as u64; }
+
+    let bits = if v == 0. { 0 } else { v.to_bits() };
+
+    match bits >> 31 {
+        1 => !bits as u64,
+        _ => (bits | (1 << 31)) as u64,
+    }
+}
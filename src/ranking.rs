@@ -0,0 +1,149 @@
+use crate::error::XiError;
+use crate::xicor::{
+    argsort_ties, as_ordered, check_lengths, expected_rank_diff_sum, permute,
+    ranks_ordered, Ranks, TiePolicy,
+};
+use num_traits::float::FloatCore;
+
+
+
+/// The ordering of an x sequence, computed once so that it can be correlated
+/// against many y sequences.
+///
+/// Most of the work in computing xi is sorting x and sorting y. When the same
+/// x is paired with many targets, for example when screening features, an
+/// `XRanking` sorts x only once, so each additional target only pays for
+/// sorting its y values. The tie policy is fixed when the ranking is built, so
+/// [`TiePolicy::Random`] orders the tied x values identically for every
+/// target.
+///
+/// # Example
+///
+/// ```
+/// use xicor::{xicor, TiePolicy, XRanking};
+///
+/// let x: Vec<u32> = (0..100).map(|i| (i*37)%100).collect();
+/// let ranking = XRanking::new(&x, TiePolicy::Stable);
+///
+/// for k in 1..10 {
+///     let y: Vec<u32> = x.iter().map(|x| (x*k)%17).collect();
+///
+///     assert_eq!(ranking.xi_with(&y), xicor(&x, &y));
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct XRanking {
+    order: Vec<usize>,
+    groups: Vec<usize>,
+    ties: TiePolicy,
+}
+
+impl XRanking {
+    /// Rank a floating-point x sequence, with tied values ordered according
+    /// to the given policy. NaN is treated as larger than every number, as in
+    /// [`xicorf`].
+    ///
+    /// [`xicorf`]: crate::xicorf
+    pub fn newf<F: FloatCore>(x: &[F], ties: TiePolicy) -> Self {
+        Self::new(as_ordered(x), ties)
+    }
+
+    /// Rank an x sequence whose values are orderable (they implement [`Ord`]),
+    /// with tied values ordered according to the given policy.
+    pub fn new<X: Ord>(x: &[X], ties: TiePolicy) -> Self {
+        let order = argsort_ties(x, ties);
+        let mut groups = vec![0; order.len()];
+
+        // Label each position in sorted order with the dense rank of its x
+        // value, which is all the expected tie policy needs to know about x
+        for k in 1..order.len() {
+            groups[k] = groups[k-1]+(x[order[k-1]] != x[order[k]]) as usize;
+        }
+
+        Self { order, groups, ties }
+    }
+
+    /// The number of x values that were ranked.
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    /// Whether the ranked x sequence is empty.
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    /// The tie policy used to order tied x values.
+    pub fn ties(&self) -> TiePolicy {
+        self.ties
+    }
+
+    /// Calculate the xi-correlation of the ranked x sequence and a
+    /// floating-point y sequence.
+    ///
+    /// # Panics
+    ///
+    /// If `y` differs in length from the ranked x sequence.
+    pub fn xi_withf<F: FloatCore>(&self, y: &[F]) -> f64 {
+        self.xi_with(as_ordered(y))
+    }
+
+    /// Calculate the xi-correlation of the ranked x sequence and a y sequence
+    /// whose values are orderable (they implement [`Ord`]).
+    ///
+    /// The result is exactly that of [`xicor_ties`] with the same tie policy.
+    ///
+    /// [`xicor_ties`]: crate::xicor_ties
+    ///
+    /// # Panics
+    ///
+    /// If `y` differs in length from the ranked x sequence.
+    pub fn xi_with<Y: Ord>(&self, y: &[Y]) -> f64 {
+        assert!(self.len() == y.len(), "x and y must have the same length");
+
+        let ranks = ranks_ordered(&permute(y, &self.order));
+
+        match self.ties {
+            TiePolicy::Expected => self.expected_xi(&ranks),
+            _ => ranks.xi(),
+        }
+    }
+
+    /// Calculate the xi-correlation of the ranked x sequence and a y sequence
+    /// whose values are orderable (they implement [`Ord`]), returning an error
+    /// instead of panicking or producing NaN for unsuitable data.
+    ///
+    /// See [`try_xicor`] for the errors reported.
+    ///
+    /// [`try_xicor`]: crate::try_xicor
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use xicor::{TiePolicy, XRanking, XiError};
+    ///
+    /// let ranking = XRanking::new(&[3, 1, 2], TiePolicy::Stable);
+    ///
+    /// assert_eq!(ranking.try_xi_with(&[5, 5, 5]), Err(XiError::ConstantY));
+    /// assert_eq!(
+    ///     ranking.try_xi_with(&[1, 2]),
+    ///     Err(XiError::LengthMismatch { x: 3, y: 2 }),
+    /// );
+    /// ```
+    pub fn try_xi_with<Y: Ord>(&self, y: &[Y]) -> Result<f64, XiError> {
+        check_lengths(&self.order, y)?;
+
+        let ranks = ranks_ordered(&permute(y, &self.order));
+        let xi = ranks.try_xi()?;
+
+        match self.ties {
+            TiePolicy::Expected => Ok(self.expected_xi(&ranks)),
+            _ => Ok(xi),
+        }
+    }
+
+    // Xi averaged over every ordering of the tied x values.
+    fn expected_xi(&self, ranks: &Ranks) -> f64 {
+        ranks.xi_with_sum(expected_rank_diff_sum(&self.groups, &ranks.rs))
+    }
+}
@@ -0,0 +1,453 @@
+use crate::compactor::Compactor;
+#[cfg(feature = "serde")]
+use crate::error::XiError;
+use crate::xicor::XiRatio;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+
+
+/// An estimate of the xi-correlation from an [`XiSketch`], with bounds that are
+/// guaranteed to contain the exact value.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct XiEstimate {
+    /// The estimated xi-correlation. This is exactly what [`xicor`] would
+    /// return while the sketch has not yet needed to approximate.
+    ///
+    /// [`xicor`]: crate::xicor
+    pub xi: f64,
+    /// A lower bound on the exact xi-correlation.
+    pub lower: f64,
+    /// An upper bound on the exact xi-correlation.
+    pub upper: f64,
+    /// The number of pairs pushed.
+    pub n: usize,
+    /// The number of pairs which arrived too late for the reorder buffer to
+    /// put them in their place in x order. For merged sketches, this includes
+    /// a bound on the number of pairs of each stream whose x values fall
+    /// below the largest x of the streams before it.
+    pub late: usize,
+}
+
+impl XiEstimate {
+    /// The largest possible difference between the estimate and the exact
+    /// xi-correlation.
+    pub fn error(&self) -> f64 {
+        (self.xi-self.lower).max(self.upper-self.xi)
+    }
+}
+
+/// An approximate xi-correlation for streams too large to hold in memory.
+///
+/// Exact xi needs every pair, both to sort by x and to rank every y value
+/// among all the others. An `XiSketch` instead makes one pass over a stream of
+/// pairs using a fixed amount of memory:
+///
+/// - Pairs first pass through a reorder buffer holding the `buffer` smallest
+///   x values seen, and leave it in ascending order of x. Streams that are
+///   already roughly ordered by x, such as time series, are thereby sorted
+///   exactly, and any pair which arrives after larger x values have left the
+///   buffer is counted as late and placed where it arrived.
+/// - Deterministic quantile sketches summarise the y values, and the lower and
+///   upper y values of every consecutive pair leaving the buffer. The sum of
+///   `|r_i - r_{i+1}|` is the number of y values falling between the two
+///   values of each consecutive pair, and the sum of `l_i(n - l_i)` depends
+///   only on the ranks of y, so both sums are estimated from the sketches
+///   alone. A fourth sketch of the x values is only used by
+///   [`merge`](Self::merge).
+///
+/// Each sketch is a stack of compactors in the style of the KLL sketch, whose
+/// top level holds `k` values and whose lower levels shrink geometrically, so
+/// a sketch never holds more than `3k + 128` values. The whole structure
+/// therefore takes `O(buffer + k)` space, independent of the stream length.
+/// Floats can be used by wrapping them in [`OrderedFloat`]. With the `serde`
+/// feature enabled, sketches can be serialised, and deserialising one that is
+/// inconsistent fails with [`XiError::InvalidSummary`].
+///
+/// [`XiError::InvalidSummary`]: crate::XiError::InvalidSummary
+///
+/// # Error guarantee
+///
+/// Until a sketch fills up, its ranks are exact, so while fewer than `k` pairs
+/// have been pushed and none were late, [`XiEstimate::xi`] is exactly the
+/// result of [`xicor`] on the pairs in the order they were pushed. Beyond
+/// that, each sketch tracks a deterministic bound on the error of its ranks.
+/// No deterministic summary of fixed size can keep this bound to a fixed
+/// fraction of the values, and here it grows to about `0.7 (n/k)^0.59/k`
+/// times the number of values. These bounds, together with at most `2n` of
+/// error in the rank difference sum per late pair, are propagated through the
+/// formula for xi to give [`XiEstimate::lower`] and [`XiEstimate::upper`],
+/// which always contain the exact xi of the pairs in the order they were
+/// pushed.
+///
+/// For continuous data without late pairs, write `e` for the relative rank
+/// error. The sum of `l(n - l)` is then about `n^3/6`, and [`finish`] widens it
+/// by `1.5 n^2` times the rank error of y, which is `9e` of it. The estimate
+/// lies within about `21e` of the upper bound and `21e/(1 - 9e)` of the lower
+/// bound, so both are about `21e` only while `9e` is small, and the lower
+/// bound is `-inf` once `e` reaches `1/9`. For example, `k = 262144` keeps both
+/// within about `0.01` for a billion pairs, while `k = 1024` with four million
+/// pairs allows a lower bound more than `10` below the estimate. These bounds
+/// are worst cases, and the actual error is typically far smaller.
+///
+/// Each late pair adds up to `2n` to the error of the rank difference sum. If
+/// x arrives out of order and overflows the reorder buffer, most pairs are
+/// late and the bounds become trivial, such as `[-inf, 1]` or a lower bound
+/// far below the least possible xi.
+///
+/// [`finish`]: Self::finish
+/// [`xicor`]: crate::xicor
+/// [`OrderedFloat`]: ordered_float::OrderedFloat
+///
+/// # Example
+///
+/// ```
+/// use xicor::{xicor, XiSketch};
+///
+/// let x: Vec<u32> = (0..20000).collect();
+/// let y: Vec<u32> = x.iter().map(|x| (x*x)%10007).collect();
+/// let mut sketch = XiSketch::new(1024, 64);
+///
+/// for (&x, &y) in x.iter().zip(&y) { sketch.push(x, y); }
+///
+/// let estimate = sketch.estimate();
+/// let exact = xicor(&x, &y);
+///
+/// assert!(estimate.lower <= exact && exact <= estimate.upper);
+/// assert!((estimate.xi-exact).abs() < 0.02);
+/// ```
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(
+    try_from = "XiSketchParts<X, Y>",
+    bound(deserialize = "X: Ord + serde::Deserialize<'de>, Y: Ord + serde::Deserialize<'de>"),
+))]
+pub struct XiSketch<X, Y> {
+    // The pairs awaiting release in x order, with their arrival numbers
+    buffer: BinaryHeap<Reverse<(X, u64, Y)>>,
+    buffer_len: usize,
+    pushed: u64,
+    // The largest key released from the buffer so far
+    max_key: Option<(X, u64)>,
+    // The smallest x value released so far
+    min_x: Option<X>,
+    // The y values of the first and last pairs released
+    first_y: Option<Y>,
+    last_y: Option<Y>,
+    late: u64,
+    xs: Compactor<X>,
+    ys: Compactor<Y>,
+    lows: Compactor<Y>,
+    highs: Compactor<Y>,
+}
+
+impl<X: Ord + Clone, Y: Ord + Clone> XiSketch<X, Y> {
+    /// Create an empty sketch. The top level of each quantile sketch holds `k`
+    /// values, and the reorder buffer holds up to `buffer` pairs.
+    pub fn new(k: usize, buffer: usize) -> Self {
+        Self {
+            buffer: BinaryHeap::with_capacity(buffer+1),
+            buffer_len: buffer,
+            pushed: 0,
+            max_key: None,
+            min_x: None,
+            first_y: None,
+            last_y: None,
+            late: 0,
+            xs: Compactor::new(k),
+            ys: Compactor::new(k),
+            lows: Compactor::new(k),
+            highs: Compactor::new(k),
+        }
+    }
+
+    /// The number of pairs pushed.
+    pub fn len(&self) -> usize {
+        self.pushed as usize
+    }
+
+    /// Whether no pairs have been pushed.
+    pub fn is_empty(&self) -> bool {
+        self.pushed == 0
+    }
+
+    /// Add a pair to the stream.
+    pub fn push(&mut self, x: X, y: Y) {
+        self.buffer.push(Reverse((x, self.pushed, y)));
+        self.pushed += 1;
+
+        if self.buffer.len() > self.buffer_len {
+            let Reverse(pair) = self.buffer.pop().unwrap();
+
+            self.release(pair);
+        }
+    }
+
+    /// Absorb the sketch of a stream which follows this one, after which this
+    /// sketches the pairs of both streams in turn.
+    ///
+    /// Both reorder buffers are emptied into the quantile sketches first, so
+    /// merging suits streams split into consecutive chunks, such as the
+    /// partitions of a time series. Pairs of the later stream whose x values
+    /// fall below the largest x of this one cannot be put in their place, and
+    /// are counted as late. When the x values of the streams overlap, their
+    /// number is bounded using the sketches of x, and otherwise none are late.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use xicor::{xicor, XiSketch};
+    ///
+    /// let x: Vec<u32> = (0..20000).collect();
+    /// let y: Vec<u32> = x.iter().map(|x| (x*x)%10007).collect();
+    /// let mut sketches = x.chunks(5000).zip(y.chunks(5000)).map(|(x, y)| {
+    ///     let mut sketch = XiSketch::new(1024, 0);
+    ///
+    ///     for (&x, &y) in x.iter().zip(y) { sketch.push(x, y); }
+    ///
+    ///     sketch
+    /// });
+    ///
+    /// let mut total = sketches.next().unwrap();
+    ///
+    /// for sketch in sketches { total.merge(sketch); }
+    ///
+    /// let estimate = total.finish();
+    /// let exact = xicor(&x, &y);
+    ///
+    /// assert_eq!(estimate.late, 0);
+    /// assert!(estimate.lower <= exact && exact <= estimate.upper);
+    /// ```
+    pub fn merge(&mut self, mut other: Self) {
+        self.flush();
+        other.flush();
+
+        // The pairs out of place are those of the other stream below the
+        // largest x of this one, which is an upper bound on their number
+        let overlap = match (&self.max_key, &other.min_x) {
+            (Some((max_x, _)), Some(min_x)) if min_x < max_x => {
+                let below = other.xs.count_below(max_x)+other.xs.error() as u128;
+
+                below.min(other.pushed as u128) as u64
+            },
+            _ => 0,
+        };
+
+        if let (Some(last), Some(first)) = (&self.last_y, &other.first_y) {
+            let (low, high) = if last <= first { (last, first) } else { (first, last) };
+
+            self.lows.push(low.clone());
+            self.highs.push(high.clone());
+        }
+
+        // The arrival numbers of the other stream follow those of this one
+        let other_key = other.max_key.map(|(x, i)| (x, i+self.pushed));
+
+        self.max_key = self.max_key.take().max(other_key);
+        self.min_x = match (self.min_x.take(), other.min_x) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        self.first_y = self.first_y.take().or(other.first_y);
+        self.last_y = other.last_y.or(self.last_y.take());
+        self.pushed += other.pushed;
+        self.xs.merge(other.xs);
+        self.ys.merge(other.ys);
+        self.lows.merge(other.lows);
+        self.highs.merge(other.highs);
+
+        // A pair may be late in its own stream and out of place here too
+        self.late = (self.late+other.late+overlap).min(self.ys.len());
+    }
+
+    /// Estimate the xi-correlation of every pair pushed so far.
+    ///
+    /// The pairs still in the reorder buffer must be released into the
+    /// sketches, so this works on a copy of the sketch, taking time and memory
+    /// proportional to its size. Use [`finish`](Self::finish) at the end of a
+    /// stream to avoid the copy.
+    pub fn estimate(&self) -> XiEstimate {
+        self.clone().finish()
+    }
+
+    /// Estimate the xi-correlation of every pair pushed, consuming the sketch.
+    pub fn finish(mut self) -> XiEstimate {
+        self.flush();
+
+        let n = self.ys.len() as u128;
+        let ys = self.ys.weighted();
+
+        // The rank difference of a consecutive pair is the number of y values
+        // above its lower value and no higher than its upper value
+        let rsum = count_less(&self.lows.weighted(), &ys) as i128
+            -count_less(&self.highs.weighted(), &ys) as i128;
+        let rsum = rsum.max(0) as u128;
+        let lsum = lsum(&ys, n);
+        let xi = XiRatio::new(n as usize, rsum, lsum).to_f64();
+
+        let nf = n as f64;
+        let (err_y, err_low, err_high) = (
+            self.ys.error() as f64,
+            self.lows.error() as f64,
+            self.highs.error() as f64,
+        );
+
+        // An error of e in a rank count changes a sum over m values of it by
+        // at most m*e, and replacing the values being summed with a sketch of
+        // them changes the sum by at most the variation of the summand times
+        // the sketch's error
+        let rsum_err = nf*(err_low+err_high)+2.*(nf-1.).max(0.)*err_y
+            +2.*nf*self.late as f64;
+        let lsum_err = 1.5*nf*nf*err_y;
+        let (lower, upper) = match (rsum_err, lsum_err) {
+            (0., 0.) => (xi, xi),
+            _ if xi.is_nan() => (f64::NAN, f64::NAN),
+            _ => {
+                let (rsum, lsum) = (rsum as f64, lsum as f64);
+                let lsum_min = lsum-lsum_err;
+                let lower = match lsum_min > 0. {
+                    true => 1.-nf*(rsum+rsum_err)/(2.*lsum_min),
+                    false => f64::NEG_INFINITY,
+                };
+                let upper = 1.-nf*(rsum-rsum_err).max(0.)/(2.*(lsum+lsum_err));
+
+                (lower.min(xi), upper.max(xi))
+            },
+        };
+
+        XiEstimate { xi, lower, upper, n: n as usize, late: self.late as usize }
+    }
+
+    // Release every pair left in the reorder buffer.
+    fn flush(&mut self) {
+        while let Some(Reverse(pair)) = self.buffer.pop() { self.release(pair); }
+    }
+
+    // Pass a pair from the reorder buffer into the sketches.
+    fn release(&mut self, (x, i, y): (X, u64, Y)) {
+        // Pairs which are not late form an ascending run, so sorting the
+        // stream only needs the late ones to be moved
+        if self.min_x.as_ref().is_none_or(|min_x| x < *min_x) {
+            self.min_x = Some(x.clone());
+        }
+
+        self.xs.push(x.clone());
+
+        match &self.max_key {
+            Some((max_x, max_i)) if (&x, i) < (max_x, *max_i) => self.late += 1,
+            _ => self.max_key = Some((x, i)),
+        }
+
+        if let Some(last) = &self.last_y {
+            let (low, high) = if *last <= y { (last, &y) } else { (&y, last) };
+
+            self.lows.push(low.clone());
+            self.highs.push(high.clone());
+        }
+
+        if self.first_y.is_none() { self.first_y = Some(y.clone()); }
+
+        self.ys.push(y.clone());
+        self.last_y = Some(y);
+    }
+}
+
+// The serialised fields of a sketch, which are checked for consistency before
+// a sketch is built from them.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(bound(deserialize = "X: Ord + serde::Deserialize<'de>, Y: Ord + serde::Deserialize<'de>"))]
+struct XiSketchParts<X, Y> {
+    buffer: BinaryHeap<Reverse<(X, u64, Y)>>,
+    buffer_len: usize,
+    pushed: u64,
+    max_key: Option<(X, u64)>,
+    min_x: Option<X>,
+    first_y: Option<Y>,
+    last_y: Option<Y>,
+    late: u64,
+    xs: Compactor<X>,
+    ys: Compactor<Y>,
+    lows: Compactor<Y>,
+    highs: Compactor<Y>,
+}
+
+#[cfg(feature = "serde")]
+impl<X: Ord, Y: Ord> TryFrom<XiSketchParts<X, Y>> for XiSketch<X, Y> {
+    type Error = XiError;
+
+    fn try_from(parts: XiSketchParts<X, Y>) -> Result<Self, XiError> {
+        // Every pair pushed is either buffered or released into the sketches,
+        // and every released pair but the first follows another
+        let released = parts.ys.len();
+        let consecutive = released.saturating_sub(1);
+        let valid = parts.buffer.len() <= parts.buffer_len
+            && parts.pushed == released+parts.buffer.len() as u64
+            && parts.late <= released
+            && parts.xs.len() == released
+            && parts.lows.len() == consecutive
+            && parts.highs.len() == consecutive
+            && [
+                parts.max_key.is_some(),
+                parts.min_x.is_some(),
+                parts.first_y.is_some(),
+                parts.last_y.is_some(),
+            ].iter().all(|&some| some == (released > 0));
+
+        if !valid { return Err(XiError::InvalidSummary); }
+
+        Ok(Self {
+            buffer: parts.buffer,
+            buffer_len: parts.buffer_len,
+            pushed: parts.pushed,
+            max_key: parts.max_key,
+            min_x: parts.min_x,
+            first_y: parts.first_y,
+            last_y: parts.last_y,
+            late: parts.late,
+            xs: parts.xs,
+            ys: parts.ys,
+            lows: parts.lows,
+            highs: parts.highs,
+        })
+    }
+}
+
+// The weighted number of pairs of values, one from each list, in which the
+// value from the first list is less than that from the second. Both lists
+// must be in ascending order.
+fn count_less<T: Ord>(a: &[(&T, u128)], b: &[(&T, u128)]) -> u128 {
+    let mut below = 0;
+    let mut i = 0;
+    let mut total = 0;
+
+    for &(v, w) in b {
+        while i < a.len() && a[i].0 < v {
+            below += a[i].1;
+            i += 1;
+        }
+
+        total += w*below;
+    }
+
+    total
+}
+
+// The sum of l_i(n - l_i) over weighted values in ascending order, where
+// n - l_i is the weight of the values below the ith.
+fn lsum<T: Ord>(ys: &[(&T, u128)], n: u128) -> u128 {
+    let mut below = 0;
+    let mut start = 0;
+    let mut total = 0;
+
+    while start < ys.len() {
+        let len = ys[start..].partition_point(|&(v, _)| v == ys[start].0);
+        let weight: u128 = ys[start..start+len].iter().map(|&(_, w)| w).sum();
+
+        total += weight*below*(n-below);
+        below += weight;
+        start += len;
+    }
+
+    total
+}
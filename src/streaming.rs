@@ -0,0 +1,233 @@
+use crate::independence::{standardise, tie_corrected_variance};
+use crate::independence::{NullVariance, XiTest};
+use crate::treap::Multiset;
+use crate::xicor::XiRatio;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Bound::{Excluded, Unbounded};
+
+
+
+/// The xi-correlation of a changing collection of pairs, updated incrementally
+/// as pairs are inserted and removed.
+///
+/// Recomputing [`xicor`] whenever a pair arrives costs `O(n log n)`. A
+/// `StreamingXi` instead maintains both sums in the formula for xi as pairs come
+/// and go, at a cost of `O(log n)` per update. The y values are kept in
+/// order-statistic trees, which give the ranks `r_i` and the change in
+/// `sum l_i(n - l_i)` directly. Inserting a y value raises `|r_i - r_{i+1}|` by
+/// exactly 1 for every consecutive pair (in x order) which straddles it, so
+/// the lower and upper y values of the consecutive pairs are kept in two more
+/// trees, whose ranks count the straddling pairs.
+///
+/// Pairs with tied x values are ordered by insertion, so [`value`] is exactly
+/// [`xicor`] of the current pairs listed in the order they were inserted.
+/// Floats can be used by wrapping them in [`OrderedFloat`].
+///
+/// [`xicor`]: crate::xicor
+/// [`value`]: Self::value
+/// [`OrderedFloat`]: ordered_float::OrderedFloat
+///
+/// # Example
+///
+/// ```
+/// use xicor::{xicor, StreamingXi};
+///
+/// let mut stream = StreamingXi::new();
+///
+/// for t in 0..100u32 {
+///     stream.insert(t, (t*t)%17);
+///
+///     // Keep a sliding window of the last 50 pairs
+///     if t >= 50 { stream.remove(&(t-50), &(((t-50)*(t-50))%17)); }
+/// }
+///
+/// let x: Vec<u32> = (50..100).collect();
+/// let y: Vec<u32> = x.iter().map(|x| (x*x)%17).collect();
+///
+/// assert_eq!(stream.len(), 50);
+/// assert_eq!(stream.value(), xicor(&x, &y));
+/// ```
+pub struct StreamingXi<X, Y> {
+    // The y value of every pair, keyed by x and then insertion number
+    by_x: BTreeMap<(X, u64), Y>,
+    // Every pair with its insertion number, for finding pairs to remove
+    pairs: BTreeSet<(X, Y, u64)>,
+    ys: Multiset<Y>,
+    // The lower and upper y values of every consecutive pair in x order
+    lows: Multiset<Y>,
+    highs: Multiset<Y>,
+    rsum: u128,
+    lsum: u128,
+    inserted: u64,
+}
+
+impl<X: Ord + Clone, Y: Ord + Clone> Default for StreamingXi<X, Y> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<X: Ord + Clone, Y: Ord + Clone> StreamingXi<X, Y> {
+    /// Create an empty collection of pairs.
+    pub fn new() -> Self {
+        Self {
+            by_x: BTreeMap::new(),
+            pairs: BTreeSet::new(),
+            ys: Multiset::new(),
+            lows: Multiset::new(),
+            highs: Multiset::new(),
+            rsum: 0,
+            lsum: 0,
+            inserted: 0,
+        }
+    }
+
+    /// The number of pairs currently held.
+    pub fn len(&self) -> usize {
+        self.by_x.len()
+    }
+
+    /// Whether no pairs are currently held.
+    pub fn is_empty(&self) -> bool {
+        self.by_x.is_empty()
+    }
+
+    /// The xi-correlation of the pairs currently held. This is NaN if there
+    /// are fewer than 2 pairs or every y value is the same, as for [`xicor`].
+    ///
+    /// [`xicor`]: crate::xicor
+    pub fn value(&self) -> f64 {
+        self.ratio().to_f64()
+    }
+
+    /// The xi-correlation of the pairs currently held, as an exact fraction.
+    pub fn ratio(&self) -> XiRatio {
+        XiRatio::new(self.len(), self.rsum, self.lsum)
+    }
+
+    /// Test the pairs currently held for independence, exactly as
+    /// [`xicor_test`] would.
+    ///
+    /// Without ties in y this takes constant time. With ties, the
+    /// tie-corrected variance needs every rank, which takes time proportional
+    /// to the number of pairs.
+    ///
+    /// [`xicor_test`]: crate::xicor_test
+    pub fn test(&self) -> XiTest {
+        let n = self.len();
+
+        // The squared counts only sum to n when every count is 1
+        let null_variance = match self.ys.squares() > n as u128 {
+            true => {
+                let mut us = Vec::with_capacity(n);
+
+                for count in self.ys.counts() {
+                    us.resize(us.len()+count, us.len()+count);
+                }
+
+                NullVariance::TieCorrected(tie_corrected_variance(&us, self.lsum))
+            },
+            false => NullVariance::Continuous,
+        };
+
+        standardise(self.value(), n, null_variance)
+    }
+
+    /// Add a pair. If other pairs share its x value, it is placed after them.
+    pub fn insert(&mut self, x: X, y: Y) {
+        self.lsum += self.lsum_change(&y);
+        self.ys.insert(y.clone());
+
+        // The new value raises r by 1 for every y value at least as large, so
+        // each consecutive pair straddling it grows its difference by 1
+        self.rsum += (self.lows.count_lt(&y)-self.highs.count_lt(&y)) as u128;
+        self.inserted += 1;
+
+        let key = (x, self.inserted);
+        let (prev, next) = self.neighbours(&key);
+
+        if let (Some(p), Some(n)) = (&prev, &next) { self.unlink(p, n); }
+        if let Some(p) = &prev { self.link(p, &y); }
+        if let Some(n) = &next { self.link(&y, n); }
+
+        self.pairs.insert((key.0.clone(), y.clone(), key.1));
+        self.by_x.insert(key, y);
+    }
+
+    /// Remove a pair, returning whether it was present. If the same pair was
+    /// inserted more than once, the earliest remaining copy is removed.
+    pub fn remove(&mut self, x: &X, y: &Y) -> bool {
+        let start = (x.clone(), y.clone(), 0);
+        let Some(found) = self.pairs.range(start..).next() else { return false };
+
+        if found.0 != *x || found.1 != *y { return false; }
+
+        let found = found.clone();
+        let key = (found.0.clone(), found.2);
+        let (prev, next) = self.neighbours(&key);
+
+        // Undo the steps of insertion in reverse order
+        if let Some(p) = &prev { self.unlink(p, y); }
+        if let Some(n) = &next { self.unlink(y, n); }
+        if let (Some(p), Some(n)) = (&prev, &next) { self.link(p, n); }
+
+        self.pairs.remove(&found);
+        self.by_x.remove(&key);
+        self.ys.remove(y);
+        self.rsum -= (self.lows.count_lt(y)-self.highs.count_lt(y)) as u128;
+        self.lsum -= self.lsum_change(y);
+
+        true
+    }
+
+    // The y values of the pairs before and after the given key in x order.
+    fn neighbours(&self, key: &(X, u64)) -> (Option<Y>, Option<Y>) {
+        let prev = self.by_x.range((Unbounded, Excluded(key))).next_back();
+        let next = self.by_x.range((Excluded(key), Unbounded)).next();
+
+        (prev.map(|(_, y)| y.clone()), next.map(|(_, y)| y.clone()))
+    }
+
+    // Make a and b consecutive in x order.
+    fn link(&mut self, a: &Y, b: &Y) {
+        let (lo, hi) = if a <= b { (a, b) } else { (b, a) };
+
+        self.rsum += self.rank_diff(lo, hi);
+        self.lows.insert(lo.clone());
+        self.highs.insert(hi.clone());
+    }
+
+    // Stop a and b from being consecutive in x order.
+    fn unlink(&mut self, a: &Y, b: &Y) {
+        let (lo, hi) = if a <= b { (a, b) } else { (b, a) };
+
+        self.rsum -= self.rank_diff(lo, hi);
+        self.lows.remove(lo);
+        self.highs.remove(hi);
+    }
+
+    // The difference between the r values of two y values, where lo <= hi.
+    fn rank_diff(&self, lo: &Y, hi: &Y) -> u128 {
+        (self.ys.below_le(hi).0-self.ys.below_le(lo).0) as u128
+    }
+
+    // The increase in the sum of l_i(n - l_i) when y is added to the current
+    // y values.
+    //
+    // Adding y raises l by 1 for every value at or below y, which raises its
+    // term by n - l, the number of values strictly below it. Summed over the m
+    // values at or below y, that is the number of pairs of them with distinct
+    // values, (m^2 - sum of squared counts)/2. For the a values above y, n
+    // grows while l stays the same, raising each term by l, and summing l over
+    // them counts the pairs of them in non-decreasing order,
+    // (a^2 + sum of squared counts)/2. Finally y contributes its own term.
+    fn lsum_change(&self, y: &Y) -> u128 {
+        let n = self.ys.len() as u128;
+        let (m, squares_le) = self.ys.below_le(y);
+        let (m, lt) = (m as u128, self.ys.count_lt(y) as u128);
+        let a = n-m;
+        let squares_gt = self.ys.squares()-squares_le;
+
+        (m*m-squares_le)/2+(a*a+squares_gt)/2+(n-lt+1)*lt
+    }
+}
@@ -0,0 +1,282 @@
+#[cfg(feature = "serde")]
+use crate::error::XiError;
+use crate::sketch::{XiEstimate, XiSketch};
+use crate::xicor::{argsort, as_ordered, ranks_ordered, XiRatio};
+#[cfg(feature = "serde")]
+use crate::xicor::check_lengths;
+use num_traits::float::FloatCore;
+use ordered_float::OrderedFloat;
+
+
+
+/// A partial state from which xi can be computed exactly, which can be merged
+/// with the states of other partitions of the data.
+///
+/// When the pairs are spread across many workers, each can build a summary of
+/// its own partition and send it onwards, where the summaries are combined with
+/// [`merge`] and the global xi read off with [`value`]. A summary is the
+/// partition's pairs arranged as a single run in ascending order of x, so
+/// merging two summaries is a linear-time merge of their runs, and no summary
+/// ever needs to be sorted again. Merging can happen in any tree shape, so
+/// long as the partitions stay in the same order: the result is exactly that
+/// of [`xicor`] on the partitions concatenated in that order, with tied x
+/// values from an earlier partition placed before those from a later one.
+///
+/// Being exact, a summary holds every pair of its partition, so its memory and
+/// serialised size are `O(n)`. When that is too much to send, an
+/// [`XiCompressedSummary`] estimates xi within bounds using `O(k)` memory.
+///
+/// With the `serde` feature enabled, summaries can be serialised for sending
+/// between processes. Deserialising a summary whose x and y differ in length,
+/// or whose x values are not in ascending order, fails with an [`XiError`].
+///
+/// [`merge`]: Self::merge
+/// [`value`]: Self::value
+/// [`xicor`]: crate::xicor
+/// [`XiError`]: crate::XiError
+///
+/// # Example
+///
+/// ```
+/// use xicor::{xicor, XiSummary};
+///
+/// let x: Vec<u32> = (0..1000).map(|i| (i*7919)%1000).collect();
+/// let y: Vec<u32> = x.iter().map(|x| (x*x)%97).collect();
+///
+/// // Each worker summarises its own chunk of the data
+/// let mut summaries = x.chunks(300).zip(y.chunks(300))
+///     .map(|(x, y)| XiSummary::new(x, y));
+///
+/// let mut total = summaries.next().unwrap();
+///
+/// for summary in summaries { total.merge(summary); }
+///
+/// assert_eq!(total.len(), 1000);
+/// assert_eq!(total.value(), xicor(&x, &y));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(
+    try_from = "XiSummaryParts<X, Y>",
+    bound(deserialize = "X: Ord + serde::Deserialize<'de>, Y: serde::Deserialize<'de>"),
+))]
+pub struct XiSummary<X, Y> {
+    // Both in ascending order of x, with ties in their original order
+    x: Vec<X>,
+    y: Vec<Y>,
+}
+
+impl<X, Y> Default for XiSummary<X, Y> {
+    fn default() -> Self {
+        Self { x: vec![], y: vec![] }
+    }
+}
+
+impl<FX: FloatCore, FY: FloatCore> XiSummary<OrderedFloat<FX>, OrderedFloat<FY>> {
+    /// Summarise two floating-point sequences.
+    ///
+    /// See [`new`](Self::new) for details.
+    pub fn newf(x: &[FX], y: &[FY]) -> Self {
+        Self::new(as_ordered(x), as_ordered(y))
+    }
+}
+
+impl<X: Ord + Clone, Y: Ord + Clone> XiSummary<X, Y> {
+    /// Summarise two sequences whose values are orderable (they implement
+    /// [`Ord`]).
+    ///
+    /// # Panics
+    ///
+    /// If `x` and `y` differ in length.
+    pub fn new(x: &[X], y: &[Y]) -> Self {
+        assert!(x.len() == y.len(), "x and y must have the same length");
+
+        let idcs = argsort(x);
+
+        Self {
+            x: idcs.iter().map(|&i| x[i].clone()).collect(),
+            y: idcs.iter().map(|&i| y[i].clone()).collect(),
+        }
+    }
+
+    /// The number of pairs summarised.
+    pub fn len(&self) -> usize {
+        self.x.len()
+    }
+
+    /// Whether no pairs are summarised.
+    pub fn is_empty(&self) -> bool {
+        self.x.is_empty()
+    }
+
+    /// Combine this summary with that of the partition following it.
+    pub fn merge(&mut self, other: Self) {
+        let n = self.len()+other.len();
+        let mut x = Vec::with_capacity(n);
+        let mut y = Vec::with_capacity(n);
+        let mut ours = self.x.drain(..).zip(self.y.drain(..)).peekable();
+        let mut theirs = other.x.into_iter().zip(other.y).peekable();
+
+        // Taking from this summary whenever x is tied keeps the merge stable
+        loop {
+            let next = match (ours.peek(), theirs.peek()) {
+                (Some(a), Some(b)) if a.0 <= b.0 => ours.next(),
+                (_, Some(_)) => theirs.next(),
+                (Some(_), None) => ours.next(),
+                (None, None) => break,
+            };
+            let (xi, yi) = next.unwrap();
+
+            x.push(xi);
+            y.push(yi);
+        }
+
+        drop(ours);
+        (self.x, self.y) = (x, y);
+    }
+
+    /// The xi-correlation of every pair summarised, exactly as returned by
+    /// [`xicor`].
+    ///
+    /// [`xicor`]: crate::xicor
+    pub fn value(&self) -> f64 {
+        self.ratio().to_f64()
+    }
+
+    /// The xi-correlation of every pair summarised, as an exact fraction.
+    pub fn ratio(&self) -> XiRatio {
+        ranks_ordered(&self.y).ratio()
+    }
+}
+
+// The serialised fields of a summary, which are checked before a summary is
+// built from them.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct XiSummaryParts<X, Y> {
+    x: Vec<X>,
+    y: Vec<Y>,
+}
+
+#[cfg(feature = "serde")]
+impl<X: Ord, Y> TryFrom<XiSummaryParts<X, Y>> for XiSummary<X, Y> {
+    type Error = XiError;
+
+    fn try_from(parts: XiSummaryParts<X, Y>) -> Result<Self, XiError> {
+        check_lengths(&parts.x, &parts.y)?;
+
+        match parts.x.is_sorted() {
+            true => Ok(Self { x: parts.x, y: parts.y }),
+            false => Err(XiError::InvalidSummary),
+        }
+    }
+}
+
+/// A compressed partial state from which xi can be estimated within bounds,
+/// which can be merged with the states of other partitions of the data.
+///
+/// This is the counterpart of [`XiSummary`] for partitions too large to send
+/// in full. Each partition is sorted by x and fed through an [`XiSketch`], so a
+/// summary holds at most `4(3k + 128)` values, plus a few more for its first
+/// and last pairs, however many pairs it summarises. Summaries are combined
+/// with [`merge`] and the global xi estimated with [`estimate`].
+///
+/// The estimate carries the error guarantee of an [`XiSketch`], with bounds
+/// that always contain the exact xi of the partitions concatenated in order.
+/// While the summaries merged hold no more than `k` pairs between them, the
+/// estimate is exact. Partitions whose x values follow each other, as when
+/// the data is partitioned by ranges of x or by time, merge without further
+/// error. Where the x values of consecutive partitions overlap, the pairs out
+/// of place are counted as late, each widening the bounds as described for
+/// the sketch, so heavily interleaved partitions give loose bounds.
+///
+/// With the `serde` feature enabled, summaries can be serialised, and
+/// deserialising one that is inconsistent fails with
+/// [`XiError::InvalidSummary`].
+///
+/// [`merge`]: Self::merge
+/// [`estimate`]: Self::estimate
+/// [`XiError::InvalidSummary`]: crate::XiError::InvalidSummary
+///
+/// # Example
+///
+/// ```
+/// use xicor::{xicor, XiCompressedSummary};
+///
+/// let x: Vec<u32> = (0..20000).collect();
+/// let y: Vec<u32> = x.iter().map(|x| (x*x)%10007).collect();
+///
+/// // Each worker summarises its own range of x
+/// let mut summaries = x.chunks(5000).zip(y.chunks(5000))
+///     .map(|(x, y)| XiCompressedSummary::new(x, y, 1024));
+///
+/// let mut total = summaries.next().unwrap();
+///
+/// for summary in summaries { total.merge(summary); }
+///
+/// let estimate = total.estimate();
+/// let exact = xicor(&x, &y);
+///
+/// assert_eq!(total.len(), 20000);
+/// assert!(estimate.lower <= exact && exact <= estimate.upper);
+/// ```
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(
+    bound(deserialize = "X: Ord + serde::Deserialize<'de>, Y: Ord + serde::Deserialize<'de>"),
+))]
+pub struct XiCompressedSummary<X, Y> {
+    sketch: XiSketch<X, Y>,
+}
+
+impl<FX, FY> XiCompressedSummary<OrderedFloat<FX>, OrderedFloat<FY>>
+where
+    FX: FloatCore,
+    FY: FloatCore,
+{
+    /// Summarise two floating-point sequences.
+    ///
+    /// See [`new`](Self::new) for details.
+    pub fn newf(x: &[FX], y: &[FY], k: usize) -> Self {
+        Self::new(as_ordered(x), as_ordered(y), k)
+    }
+}
+
+impl<X: Ord + Clone, Y: Ord + Clone> XiCompressedSummary<X, Y> {
+    /// Summarise two sequences whose values are orderable (they implement
+    /// [`Ord`]), using quantile sketches whose top level holds `k` values.
+    ///
+    /// # Panics
+    ///
+    /// If `x` and `y` differ in length.
+    pub fn new(x: &[X], y: &[Y], k: usize) -> Self {
+        assert!(x.len() == y.len(), "x and y must have the same length");
+
+        // Sorting first means the sketch never needs to reorder anything
+        let mut sketch = XiSketch::new(k, 0);
+
+        for i in argsort(x) { sketch.push(x[i].clone(), y[i].clone()); }
+
+        Self { sketch }
+    }
+
+    /// The number of pairs summarised.
+    pub fn len(&self) -> usize {
+        self.sketch.len()
+    }
+
+    /// Whether no pairs are summarised.
+    pub fn is_empty(&self) -> bool {
+        self.sketch.is_empty()
+    }
+
+    /// Combine this summary with that of the partition following it.
+    pub fn merge(&mut self, other: Self) {
+        self.sketch.merge(other.sketch);
+    }
+
+    /// Estimate the xi-correlation of every pair summarised.
+    pub fn estimate(&self) -> XiEstimate {
+        self.sketch.estimate()
+    }
+}
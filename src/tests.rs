@@ -1,4 +1,8 @@
 use super::*;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use ordered_float::OrderedFloat;
+use std::sync::Arc;
 
 
 
@@ -73,7 +77,7 @@ fn test_permute() {
     let idcs = [2, 7, 1, 5, 0, 4, 6, 3];
     let permuted = [-9, -3, -2, 1, 2, 4, 6, 8];
 
-    assert_eq!(permute(&arr, &idcs).as_slice(), &permuted);
+    assert_eq!(permute(&arr, &idcs), permuted.iter().collect::<Vec<_>>());
 }
 
 #[test]
@@ -91,3 +95,1125 @@ fn test_cumulative_gte() {
 
     assert_eq!(cumulative_gte(&arr).as_slice(), &counts);
 }
+
+#[test]
+fn test_xicor_test() {
+    let x = [1, 4, -9, -6, -5, -8, -1, 0, -4, -5];
+    let y = [9, 8, 5, -10, 7, -6, -2, -8, 4, 3];
+    let test = xicor_test(&x, &y);
+
+    assert_req(test.xi, 0.0909090909, RTOL);
+    assert_req(test.z, 0.4545454545, RTOL);
+    assert_req(test.p_value, 0.3247181419, RTOL);
+}
+
+#[test]
+fn test_xicorf_test() {
+    let x: Vec<f32> = (0..1000).map(|i| i as f32/1000.).collect();
+    let y: Vec<f32> = x.iter().map(|&x| (x*12.566).sin()).collect();
+    let test = xicorf_test(&x, &y);
+
+    assert_req(test.xi, 0.9880330596, RTOL);
+    assert!(test.p_value < 1e-100);
+}
+
+#[test]
+fn test_normal_sf() {
+    assert_req(normal::sf(0.), 0.5, RTOL);
+    assert_req(normal::sf(1.96), 0.024997895148220435, RTOL);
+    assert_req(normal::sf(-1.), 0.8413447460685429, RTOL);
+    assert_req(normal::sf(3.5), 2.3262907903552504e-4, RTOL);
+    assert_req(normal::sf(10.), 7.61985302416047e-24, RTOL);
+}
+
+#[test]
+fn test_xicor_test_ties() {
+    let x: Vec<i32> = (0..12).collect();
+    let y = [3, 1, 2, 3, 1, 1, 2, 3, 2, 1, 3, 3];
+    let test = xicor_test(&x, &y);
+
+    assert_req(test.xi, -0.0627306273, RTOL);
+    assert_req(test.null_variance.value(), 0.7058863578, RTOL);
+    assert_req(test.p_value, 0.6020451382, RTOL);
+    assert!(matches!(test.null_variance, NullVariance::TieCorrected(_)));
+}
+
+#[test]
+fn test_tie_corrected_variance() {
+    // Without ties the general estimator should agree with the continuous
+    // variance of 2/5
+    let x: Vec<u32> = (0..1000).map(|i| (i*7919)%1000).collect();
+    let y: Vec<u32> = (0..1000).map(|i| (i*104729)%1009).collect();
+    let ranks = ranks(&x, &y);
+    let mut us = ranks.rs;
+
+    us.sort_unstable();
+
+    assert_req(tie_corrected_variance(&us, ranks.lsum), 0.4000018000, RTOL);
+    assert_eq!(xicor_test(&x, &y).null_variance, NullVariance::Continuous);
+}
+
+#[test]
+fn test_xicor_permutation_test() {
+    let x = [1, 4, -9, -6, -5, -8, -1, 0, -4, -5];
+    let y = [9, 8, 5, -10, 7, -6, -2, -8, 4, 3];
+    let options = PermutationOptions::new(2000);
+    let test = xicor_permutation_test(&x, &y, options, &mut StdRng::seed_from_u64(7));
+
+    assert_req(test.xi, 0.0909090909, RTOL);
+    assert_eq!(test.permutations, 2000);
+
+    // The asymptotic p-value for this data is about 0.32, and with so few
+    // points the permutation p-value should be in the same region
+    assert!((test.p_value-0.3).abs() < 0.1);
+    assert_req(test.std_error, (test.p_value*(1.-test.p_value)/2000.).sqrt(), RTOL);
+
+    // The same seed must give the same result
+    let again = xicor_permutation_test(&x, &y, options, &mut StdRng::seed_from_u64(7));
+
+    assert_eq!(test, again);
+}
+
+#[test]
+fn test_xicorf_permutation_test_early_stop() {
+    let x: Vec<f32> = (0..200).map(|i| i as f32/200.).collect();
+    let y: Vec<f32> = x.iter().map(|&x| (x*12.566).sin()).collect();
+    let options = PermutationOptions::new(100000).early_stop(0.05);
+    let test = xicorf_permutation_test(&x, &y, options, &mut StdRng::seed_from_u64(7));
+
+    assert_eq!(test.permutations, 100);
+    assert_req(test.p_value, 1./101., RTOL);
+}
+
+#[test]
+fn test_xicor_exact_test() {
+    let x = [1, 4, -9, -6, -5, -8, -1, 0];
+    let y = [9, 8, 5, -10, 7, -6, -2, -8];
+    let test = xicor_exact_test(&x, &y);
+
+    assert_req(test.xi, 0.0476190476, RTOL);
+    assert_req(test.p_value, 17568./40320., RTOL);
+}
+
+#[test]
+fn test_xicor_exact_test_ties() {
+    let x: Vec<i32> = (0..8).collect();
+    let y = [2, 1, 2, 3, 1, 1, 3, 2];
+    let test = xicor_exact_test(&x, &y);
+
+    // There are only 8!/(3!3!2!) = 560 distinct arrangements of these y values
+    assert_req(test.xi, -0.1594202899, RTOL);
+    assert_req(test.p_value, 426./560., RTOL);
+}
+
+#[test]
+fn test_null_distribution() {
+    let dist = null_distribution(&[5.5, 1.5, 2.5, 4.5, 3.5].map(OrderedFloat));
+    let total = dist.support().iter().map(|(_, p)| p).sum::<f64>();
+
+    assert_eq!(dist.n(), 5);
+    assert_req(total, 1., RTOL);
+
+    // Repeated calls with the same pattern of ties share the cached table
+    assert!(Arc::ptr_eq(&dist, &null_distribution(&[10, 20, 30, 40, 50])));
+
+    for (xi, _) in dist.support() {
+        let p = dist.support().iter()
+            .filter(|&&(other, _)| other >= xi)
+            .map(|(_, p)| p)
+            .sum::<f64>();
+
+        assert_req(dist.p_value(xi), p, RTOL);
+    }
+
+    assert_eq!(dist.p_value(1.), 0.);
+    assert_eq!(dist.p_value(-1.), 1.);
+}
+
+#[test]
+fn test_null_distribution_tables() {
+    for n in 0..=MAX_EXACT_N {
+        let rs: Vec<usize> = (1..=n).collect();
+
+        assert_eq!(exact::continuous(n), exact::enumerate(&rs, &rs));
+    }
+}
+
+#[test]
+fn test_next_permutation() {
+    let mut arr = [1, 1, 2, 3];
+    let mut count = 1;
+
+    while next_permutation(&mut arr) { count += 1; }
+
+    assert_eq!(count, 12);
+    assert_eq!(arr, [3, 2, 1, 1]);
+}
+
+#[test]
+fn test_xicor_bootstrap() {
+    let x: Vec<i32> = (0..100).collect();
+    let y: Vec<i32> = x.iter().map(|x| (x*x)%17).collect();
+    let mut rng = StdRng::seed_from_u64(3);
+    let boot = xicor_bootstrap(&x, &y, 500, 0.9, &mut rng);
+
+    assert_eq!(boot.xi, xicor(&x, &y));
+    assert_eq!(boot.resamples, 500);
+    assert!(boot.std_error.unwrap() > 0.);
+
+    for ci in [boot.percentile, boot.basic, boot.bca] {
+        assert!(ci.lower < ci.upper);
+    }
+
+    assert_req(boot.basic.lower, 2.*boot.xi-boot.percentile.upper, RTOL);
+    assert_req(boot.basic.upper, 2.*boot.xi-boot.percentile.lower, RTOL);
+
+    // Resampling duplicates points, which inflates xi, so the replicates are
+    // biased upwards and the correction must pull the BCa interval downwards
+    assert!(boot.bias_correction < 0.);
+    assert!(boot.bca.lower < boot.percentile.lower);
+    assert!(boot.bca.upper < boot.percentile.upper);
+}
+
+#[test]
+fn test_xicor_bootstrap_degenerate() {
+    let x: Vec<i32> = (0..20).collect();
+    let mut rng = StdRng::seed_from_u64(3);
+
+    // No replicate is defined for constant y, nor with no resamples at all
+    let cases = [
+        xicor_bootstrap(&x, &[4; 20], 100, 0.9, &mut rng),
+        xicor_bootstrap(&x, &x, 0, 0.9, &mut rng),
+        xicor_bootstrap::<i32, i32, _>(&[], &[], 100, 0.9, &mut rng),
+    ];
+
+    for boot in cases {
+        assert_eq!(boot.resamples, 0);
+        assert!(boot.std_error.is_none());
+
+        for ci in [boot.percentile, boot.basic, boot.bca] {
+            assert!(ci.lower.is_nan() && ci.upper.is_nan());
+        }
+    }
+
+    let result = XiCorrelation::new()
+        .interval(IntervalMethod::Bca, 0.9)
+        .resamples(0)
+        .compute(&x, &x)
+        .unwrap();
+    let interval = result.interval.unwrap();
+
+    assert_eq!(result.xi, xicor(&x, &x));
+    assert!(interval.lower.is_nan() && interval.upper.is_nan());
+
+    // Leaving out the only distinct y value leaves one subsample undefined,
+    // which should not stop the BCa interval of the defined replicates
+    let mut y = [1; 20];
+
+    y[3] = 0;
+
+    let boot = xicor_bootstrap(&x, &y, 200, 0.9, &mut rng);
+
+    assert!(boot.resamples > 0);
+    assert!(boot.acceleration.is_finite());
+    assert!(boot.bca.lower.is_finite() && boot.bca.upper.is_finite());
+
+    // A single replicate gives intervals but no standard deviation
+    let boot = xicor_bootstrap(&x, &x, 1, 0.9, &mut rng);
+
+    assert_eq!(boot.resamples, 1);
+    assert_eq!(boot.std_error, None);
+    assert!(boot.percentile.lower.is_finite());
+}
+
+#[test]
+fn test_xicorf_bootstrap() {
+    let x: Vec<f64> = (0..200).map(|i| i as f64/200.).collect();
+    let y: Vec<f64> = x.iter().map(|&x| (x*12.566).sin()).collect();
+    let boot_a = xicorf_bootstrap(&x, &y, 200, 0.95, &mut StdRng::seed_from_u64(5));
+    let boot_b = xicorf_bootstrap(&x, &y, 200, 0.95, &mut StdRng::seed_from_u64(5));
+
+    assert_eq!(boot_a, boot_b);
+    assert!(boot_a.bca.contains(boot_a.xi));
+}
+
+#[test]
+fn test_quantile() {
+    let sorted = [1., 2., 4., 8.];
+
+    assert_eq!(bootstrap::quantile(&sorted, 0.), 1.);
+    assert_eq!(bootstrap::quantile(&sorted, 0.5), 3.);
+    assert_eq!(bootstrap::quantile(&sorted, 1.), 8.);
+}
+
+#[test]
+fn test_normal_quantile() {
+    for p in [1e-12, 0.001, 0.02, 0.3, 0.5, 0.9, 0.99, 1.-1e-9] {
+        assert_req(normal::cdf(normal::quantile(p)), p, 1e-12);
+    }
+
+    assert_req(normal::quantile(0.975), 1.959963984540054, RTOL);
+    assert_eq!(normal::quantile(0.), f64::NEG_INFINITY);
+}
+
+#[test]
+fn test_leave_one_out() {
+    let x = [1, 4, -9, -6, -5, -8, -1, 0, -4, -3, 7, 2];
+    let y = [9, 8, 5, -10, 8, -6, 5, -8, 4, 3, 9, 5];
+    let fast = jackknife::leave_one_out(&x, &y);
+
+    for i in 0..x.len() {
+        let x_sub = [&x[..i], &x[i+1..]].concat();
+        let y_sub = [&y[..i], &y[i+1..]].concat();
+
+        assert_req(fast[i], xicor(&x_sub, &y_sub), RTOL);
+    }
+}
+
+// The sum of l(n-l) is at most n^3/4, so it only overflows 64 bits for
+// millions of pairs, which takes tens of seconds. Run it with
+// `cargo test --release -- --ignored test_leave_one_out_large`
+#[test]
+#[ignore]
+fn test_leave_one_out_large() {
+    use rand::Rng;
+
+    // Large enough that the sums of the subsamples overflow 64 bits
+    let n = 4_000_000;
+    let mut rng = StdRng::seed_from_u64(11);
+    let x: Vec<u32> = (0..n as u32).collect();
+    let y: Vec<u32> = (0..n).map(|_| rng.gen()).collect();
+    let fast = jackknife::leave_one_out(&x, &y);
+
+    let x_sub = [&x[..1234], &x[1235..]].concat();
+    let y_sub = [&y[..1234], &y[1235..]].concat();
+
+    assert_eq!(fast[1234], xicor(&x_sub, &y_sub));
+
+    assert!(xicor_jackknife(&x, &y).bias.abs() < 1e-3);
+}
+
+#[test]
+fn test_xicor_jackknife() {
+    let x = [1, 4, -9, -6, -5, -8, -1, 0, -4, -5];
+    let y = [9, 8, 5, -10, 7, -6, -2, -8, 4, 3];
+    let jack = xicor_jackknife(&x, &y);
+
+    assert_req(jack.xi, 0.0909090909, RTOL);
+    assert_req(jack.bias_corrected, jack.xi-jack.bias, RTOL);
+    assert_req(jack.bias, -0.3231818182, RTOL);
+    assert_req(jack.std_error, 0.1999843744, RTOL);
+}
+
+#[test]
+fn test_xicorf_jackknife() {
+    let x: Vec<f32> = (0..1000).map(|i| i as f32/1000.).collect();
+    let y: Vec<f32> = x.iter().map(|&x| (x*12.566).sin()).collect();
+    let jack = xicorf_jackknife(&x, &y);
+
+    // The bias of xi for noiseless dependence is almost all of its shortfall
+    // from 1, so the corrected estimate should be very close to 1
+    assert_req(jack.xi, 0.9880330596, RTOL);
+    assert!((jack.bias_corrected-1.).abs() < 1e-4);
+}
+
+#[test]
+fn test_xicor_ties() {
+    let x = [2, 0, 1, 0, 1, 2, 0, 3, 1];
+    let y = [5, 3, 3, 1, 4, 1, 2, 6, 5];
+
+    assert_req(xicor_ties(&x, &y, TiePolicy::Stable), 0.1315789474, RTOL);
+    assert_req(xicor_ties(&x, &y, TiePolicy::Expected), 0.0526315789, RTOL);
+    assert_eq!(xicor_ties(&x, &y, TiePolicy::Stable), xicor(&x, &y));
+
+    // Averaging over many random tie-breaks should approach the expectation
+    let mean = (0..2000)
+        .map(|seed| xicor_ties(&x, &y, TiePolicy::Random(seed)))
+        .sum::<f64>()/2000.;
+
+    assert!((mean-0.0526315789).abs() < 0.01);
+}
+
+#[test]
+fn test_xicorf_ties() {
+    let x = [0., 0., 1., 1., 2., 2.];
+    let y = [0.5, 2.5, 1.5, 3.5, 4.5, 5.5];
+
+    assert_req(xicorf_ties(&x, &y, TiePolicy::Stable), 0.4, RTOL);
+    assert_req(xicorf_ties(&x, &y, TiePolicy::Expected), 8./35., RTOL);
+
+    // Reordering the input changes the stable result but not the expectation
+    let x_rev = [0., 0., 1., 1., 2., 2.];
+    let y_rev = [2.5, 0.5, 3.5, 1.5, 5.5, 4.5];
+
+    assert_req(xicorf_ties(&x_rev, &y_rev, TiePolicy::Stable), 1.-6.*12./70., RTOL);
+    assert_req(xicorf_ties(&x_rev, &y_rev, TiePolicy::Expected), 8./35., RTOL);
+}
+
+#[test]
+fn test_argsort_ties() {
+    let arr = [1, 0, 1, 0, 1, 0, 1, 0];
+
+    assert_eq!(argsort_ties(&arr, TiePolicy::Stable), [1, 3, 5, 7, 0, 2, 4, 6]);
+
+    let idcs = argsort_ties(&arr, TiePolicy::Random(3));
+    let mut zeros = idcs[..4].to_vec();
+
+    zeros.sort();
+
+    assert_eq!(zeros, [1, 3, 5, 7]);
+    assert_eq!(idcs, argsort_ties(&arr, TiePolicy::Random(3)));
+}
+
+#[test]
+fn test_xicor_mixed_types() {
+    let x = [1, 4, -9, -6, -5, -8, -1, 0, -4, -5];
+    let y = [9, 8, 5, -10, 7, -6, -2, -8, 4, 3];
+    let x_wide: Vec<i64> = x.iter().map(|&x| x as i64*1000).collect();
+    let x_str: Vec<String> = x.iter().map(|&x| format!("{:02}", x+10)).collect();
+    let x_str: Vec<&str> = x_str.iter().map(|s| s.as_str()).collect();
+    let y_f64: Vec<f64> = y.iter().map(|&y| y as f64/3.).collect();
+    let x_f32: Vec<f32> = x.iter().map(|&x| x as f32).collect();
+
+    assert_req(xicor(&x_wide, &y), 0.0909090909, RTOL);
+    assert_req(xicorf_y(&x_wide, &y_f64), 0.0909090909, RTOL);
+    assert_req(xicorf_x(&x_f32, &y), 0.0909090909, RTOL);
+    assert_req(xicorf(&x_f32, &y_f64), 0.0909090909, RTOL);
+    assert_req(xicor(&x_str, &y), 0.0909090909, RTOL);
+}
+
+#[test]
+fn test_xicor_owned_types() {
+    let x = [1, 4, -9, -6, -5, -8, -1, 0, -4, -5];
+    let y = [9, 8, 5, -10, 7, -6, -2, -8, 4, 3];
+    let x_str: Vec<String> = x.iter().map(|&x| format!("{:02}", x+10)).collect();
+    let y_bytes: Vec<Vec<u8>> = y.iter().map(|&y| vec![(y+10) as u8, 0]).collect();
+    let y_tuples: Vec<(i32, String)> = y.iter().map(|&y| (y, String::new())).collect();
+
+    assert_req(xicor(&x_str, &y_bytes), 0.0909090909, RTOL);
+    assert_req(xicor_norm(&x_str, &y_tuples), 0.125, RTOL);
+    assert_req(xicor_test(&y_bytes, &x_str).xi, xicor(&y, &x), RTOL);
+}
+
+#[test]
+fn test_xicor_by() {
+    let x = [1, 4, -9, -6, -5, -8, -1, 0, -4, -5];
+    let y = [9, 8, 5, -10, 7, -6, -2, -8, 4, 3];
+    let x_neg: Vec<i32> = x.iter().map(|x| -x).collect();
+    let y_neg: Vec<i32> = y.iter().map(|y| -y).collect();
+
+    assert_eq!(xicor_by(&x, &y, |a, b| a.cmp(b), |a, b| a.cmp(b)), xicor(&x, &y));
+
+    // Reversing both orders is the same as negating the values
+    assert_req(
+        xicor_by(&x, &y, |a, b| b.cmp(a), |a, b| b.cmp(a)),
+        xicor(&x_neg, &y_neg),
+        RTOL,
+    );
+}
+
+#[test]
+fn test_xicor_by_key() {
+    let x = [1, 4, -9, -6, -5, -8, -1, 0, -4, -5];
+    let y = [9, 8, 5, -10, 7, -6, -2, -8, 4, 3];
+    let pairs: Vec<(i32, i32)> = x.into_iter().zip(y).collect();
+
+    assert_req(xicor_by_key(&pairs, &pairs, |p| p.0, |p| p.1), 0.0909090909, RTOL);
+    assert_req(
+        xicor_by_key(&pairs, &pairs, |p| p.0.abs(), |p| p.1),
+        xicor(&x.map(i32::abs), &y),
+        RTOL,
+    );
+}
+
+#[test]
+fn test_try_xicor() {
+    let x = [1, 4, -9, -6, -5, -8, -1, 0, -4, -5];
+    let y = [9, 8, 5, -10, 7, -6, -2, -8, 4, 3];
+
+    assert_req(try_xicor(&x, &y).unwrap(), 0.0909090909, RTOL);
+    assert_eq!(
+        try_xicor(&x, &y[1..]),
+        Err(XiError::LengthMismatch { x: 10, y: 9 })
+    );
+    assert_eq!(try_xicor::<i32, i32>(&[], &[]), Err(XiError::TooFewSamples { n: 0 }));
+    assert_eq!(try_xicor(&[1], &[1]), Err(XiError::TooFewSamples { n: 1 }));
+    assert_eq!(try_xicor(&x, &[3; 10]), Err(XiError::ConstantY));
+    assert_eq!(try_xicor(&[1, 2], &[2, 1]), Ok(0.));
+}
+
+#[test]
+fn test_try_xicorf() {
+    let x: Vec<f32> = (0..1000).map(|i| i as f32/1000.).collect();
+    let mut y: Vec<f32> = x.iter().map(|&x| (x*12.566).sin()).collect();
+
+    assert_req(try_xicorf(&x, &y).unwrap(), 0.9880330596, RTOL);
+
+    y[500] = f32::NAN;
+
+    assert_eq!(try_xicorf(&x, &y), Err(XiError::NaN));
+    assert_eq!(try_xicorf(&x[1..], &y), Err(XiError::LengthMismatch { x: 999, y: 1000 }));
+}
+
+#[test]
+fn test_xicor_degenerate() {
+    // Degenerate inputs give NaN rather than panicking
+    assert!(xicor::<i32, i32>(&[], &[]).is_nan());
+    assert!(xicor(&[1], &[1]).is_nan());
+    assert!(xicor(&[1, 2, 3], &[5, 5, 5]).is_nan());
+}
+
+#[test]
+fn test_xi_error_display() {
+    let err = XiError::LengthMismatch { x: 3, y: 4 };
+
+    assert_eq!(err.to_string(), "x and y must have the same length, but have lengths 3 and 4");
+    assert_eq!(XiError::ConstantY.to_string(), "xi is undefined when y is constant");
+    assert_eq!(
+        XiError::TooManySamples { n: 12 }.to_string(),
+        "the exact test is limited to 10 samples (12 were given)"
+    );
+}
+
+#[test]
+fn test_xicorf_with_nan() {
+    let x = [0.3, f64::NAN, 0.1, 0.7, 0.5, 0.2, 0.9, 0.4];
+    let y = [2.0, 1.0, f64::NAN, 5.0, 3.0, 0.5, 4.0, 6.0];
+
+    // Highest reproduces the implicit OrderedFloat behaviour of xicorf
+    let highest = xicorf_with_nan(&x, &y, NanPolicy::Highest).unwrap();
+
+    assert_eq!(highest, PairwiseXi { xi: xicorf(&x, &y), n: 8, dropped: 0 });
+
+    // Lowest is equivalent to replacing NaN with a value below all others
+    let lowest = xicorf_with_nan(&x, &y, NanPolicy::Lowest).unwrap();
+    let x_low = x.map(|v| if v.is_nan() { -1. } else { v });
+    let y_low = y.map(|v| if v.is_nan() { -1. } else { v });
+
+    assert_eq!(lowest.xi, xicorf(&x_low, &y_low));
+
+    let dropped = xicorf_with_nan(&x, &y, NanPolicy::Drop).unwrap();
+    let x_drop = [0.3, 0.7, 0.5, 0.2, 0.9, 0.4];
+    let y_drop = [2.0, 5.0, 3.0, 0.5, 4.0, 6.0];
+
+    assert_eq!(dropped, PairwiseXi { xi: xicorf(&x_drop, &y_drop), n: 6, dropped: 2 });
+
+    let norm = xicorf_norm_with_nan(&x, &y, NanPolicy::Drop).unwrap();
+
+    assert_eq!(norm.xi, xicorf_norm(&x_drop, &y_drop));
+    assert_eq!(xicorf_with_nan(&x, &y, NanPolicy::default()), Err(XiError::NaN));
+}
+
+#[test]
+fn test_xicorf_with_nan_too_few() {
+    let x = [1., f64::NAN, 3., 4.];
+    let y = [1., 2., f64::NAN, 4.];
+
+    assert!(xicorf_with_nan(&x, &y, NanPolicy::Drop).is_ok());
+    assert_eq!(
+        xicorf_norm_with_nan(&x, &y, NanPolicy::Drop),
+        Err(XiError::TooFewSamples { n: 2 }),
+    );
+    assert_eq!(
+        xicorf_with_nan(&[f64::NAN; 3], &[1., 2., 3.], NanPolicy::Drop),
+        Err(XiError::TooFewSamples { n: 0 }),
+    );
+}
+
+#[test]
+fn test_xicor_pairwise() {
+    let x = [Some(3), None, Some(1), Some(7), Some(5), Some(2), Some(9), Some(4)];
+    let y = [Some(20), Some(10), None, None, Some(30), Some(5), Some(40), Some(60)];
+    let result = xicor_pairwise(&x, &y).unwrap();
+    let x_complete = [3, 5, 2, 9, 4];
+    let y_complete = [20, 30, 5, 40, 60];
+
+    assert_eq!(result, PairwiseXi { xi: xicor(&x_complete, &y_complete), n: 5, dropped: 3 });
+
+    // The normalisation uses the effective sample size
+    let norm = xicor_norm_pairwise(&x, &y).unwrap();
+
+    assert_eq!(norm.xi, xicor_norm(&x_complete, &y_complete));
+    assert_eq!(xicor_pairwise(&x[1..], &y), Err(XiError::LengthMismatch { x: 7, y: 8 }));
+}
+
+#[test]
+fn test_xicorf_pairwise() {
+    let x = [Some(0.3), Some(0.1), None, Some(0.7), Some(0.5f32)];
+    let y = [Some(2.0), Some(1.0), Some(3.0), Some(5.0), Some(3.0)];
+
+    assert_eq!(
+        xicorf_pairwise(&x, &y).unwrap().xi,
+        xicorf(&[0.3, 0.1, 0.7, 0.5f32], &[2.0, 1.0, 5.0, 3.0]),
+    );
+    assert_eq!(
+        xicorf_norm_pairwise(&x, &y).unwrap().xi,
+        xicorf_norm(&[0.3, 0.1, 0.7, 0.5f32], &[2.0, 1.0, 5.0, 3.0]),
+    );
+
+    let y = [Some(2.0), Some(f64::NAN), Some(3.0), Some(5.0), Some(3.0)];
+
+    assert_eq!(xicorf_pairwise(&x, &y), Err(XiError::NaN));
+}
+
+#[test]
+fn test_xi_correlation_defaults() {
+    let x: Vec<u32> = (0..200).map(|i| (i*7)%50).collect();
+    let y: Vec<u32> = (0..200).map(|i| (i*i)%61).collect();
+    let result = XiCorrelation::new().compute(&x, &y).unwrap();
+
+    assert_eq!(result.xi, xicor_ties(&x, &y, TiePolicy::Stable));
+    assert_eq!(result.xi_norm, result.xi*201./198.);
+    assert_eq!(result.statistic(), result.xi);
+    assert_eq!(result.ties_x, 150);
+    assert_eq!(result.ties_y, 200-y.iter().collect::<std::collections::HashSet<_>>().len());
+    assert_eq!((result.std_error, result.p_value, result.interval), (None, None, None));
+
+    for ties in [TiePolicy::Random(3), TiePolicy::Expected] {
+        let result = XiCorrelation::new().ties(ties).compute(&x, &y).unwrap();
+
+        assert_req(result.xi, xicor_ties(&x, &y, ties), 1e-12);
+    }
+
+    assert_eq!(XiCorrelation::new().compute(&x, &[1; 200]), Err(XiError::ConstantY));
+}
+
+#[test]
+fn test_xi_correlation_inference() {
+    let x: Vec<f64> = (0..60).map(|i| i as f64/60.).collect();
+    let y: Vec<f64> = x.iter().map(|&x| (x*9.).sin()+(x*1e4).sin()).collect();
+    let result = XiCorrelation::new()
+        .normalise(true)
+        .std_error(true)
+        .p_value(PValueMethod::Asymptotic)
+        .computef(&x, &y)
+        .unwrap();
+    let scale = 61./58.;
+
+    assert_eq!(result.statistic(), result.xi_norm);
+    assert_req(result.std_error.unwrap(), scale*xicorf_jackknife(&x, &y).std_error, RTOL);
+    assert_eq!(result.p_value.unwrap(), xicorf_test(&x, &y).p_value);
+
+    // The permutation test and bootstrap share one generator seeded as given
+    let options = PermutationOptions::new(200);
+    let result = XiCorrelation::new()
+        .p_value(PValueMethod::Permutation(options))
+        .interval(IntervalMethod::Bca, 0.9)
+        .resamples(300)
+        .seed(11)
+        .computef(&x, &y)
+        .unwrap();
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(11);
+    let perm = xicorf_permutation_test(&x, &y, options, &mut rng);
+    let boot = xicorf_bootstrap(&x, &y, 300, 0.9, &mut rng);
+
+    assert_eq!(result.p_value, Some(perm.p_value));
+    assert_eq!(result.interval, Some(boot.bca));
+
+    // The exact test is refused rather than enumerating 11! orderings
+    let result = XiCorrelation::new()
+        .p_value(PValueMethod::Exact)
+        .computef(&x[..11], &y[..11]);
+
+    assert_eq!(result, Err(XiError::TooManySamples { n: 11 }));
+
+    // Two pairs have a limit of 0, so normalising them is refused
+    let result = XiCorrelation::new().normalise(true).compute(&[1, 2], &[1, 2]);
+
+    assert_eq!(result, Err(XiError::TooFewSamples { n: 2 }));
+    assert!(XiCorrelation::new().compute(&[1, 2], &[1, 2]).is_ok());
+}
+
+#[test]
+fn test_xi_correlation_nan() {
+    let x = [0.3, f64::NAN, 0.1, 0.7, 0.5, 0.2, 0.9, 0.4];
+    let y = [2.0, 1.0, 3.0, 5.0, 3.0, f64::NAN, 4.0, 6.0];
+    let xi = |nan| XiCorrelation::new().nan(nan).computef(&x, &y);
+
+    assert_eq!(xi(NanPolicy::Error), Err(XiError::NaN));
+    assert!(xi(NanPolicy::Propagate).unwrap().xi.is_nan());
+    assert_eq!(xi(NanPolicy::Highest).unwrap().xi, xicorf(&x, &y));
+
+    for nan in [NanPolicy::Drop, NanPolicy::Lowest, NanPolicy::Highest] {
+        let result = xi(nan).unwrap();
+        let expected = xicorf_with_nan(&x, &y, nan).unwrap();
+
+        assert_eq!((result.xi, result.n, result.dropped), (expected.xi, expected.n, expected.dropped));
+    }
+}
+
+#[test]
+fn test_x_ranking() {
+    let x: Vec<f32> = (0..300).map(|i| ((i*13)%40) as f32).collect();
+    let ys: Vec<Vec<f64>> = (1..6)
+        .map(|k| x.iter().map(|&x| (x as f64*k as f64).sin()).collect())
+        .collect();
+
+    for ties in [TiePolicy::Stable, TiePolicy::Random(5), TiePolicy::Expected] {
+        let ranking = XRanking::newf(&x, ties);
+
+        assert_eq!(ranking.len(), 300);
+        assert_eq!(ranking.ties(), ties);
+
+        for y in &ys {
+            assert_eq!(ranking.xi_withf(y), xicorf_ties(&x, y, ties));
+            assert_eq!(ranking.try_xi_with(as_ordered(y)), Ok(ranking.xi_withf(y)));
+        }
+    }
+
+    assert!(XRanking::new::<u8>(&[], TiePolicy::Expected).is_empty());
+    assert!(XRanking::new(&[1], TiePolicy::Expected).xi_with(&[1]).is_nan());
+}
+
+#[test]
+fn test_xicor_in() {
+    let mut ws = XiWorkspace::new();
+
+    for n in [0, 1, 2, 50, 400, 7] {
+        let x: Vec<i64> = (0..n).map(|i| (i*i*31)%23).collect();
+        let y: Vec<i64> = (0..n).map(|i| (i*17)%11-i%3).collect();
+        let x_str: Vec<String> = x.iter().map(|x| x.to_string()).collect();
+
+        let (a, b) = (xicor_in(&mut ws, &x, &y), xicor(&x, &y));
+
+        assert!(a == b || (a.is_nan() && b.is_nan()), "{a} != {b}");
+
+        // Without radix keys the ranks come from sorting indices
+        let (a, b) = (xicor_in(&mut ws, &x_str, &y), xicor(&x_str, &y));
+
+        assert!(a == b || (a.is_nan() && b.is_nan()), "{a} != {b}");
+    }
+
+    // Once grown, the workspace is reused as is
+    let capacity = ws.capacity();
+    let x: Vec<f32> = (0..300).map(|i| i as f32/300.).collect();
+    let y: Vec<f64> = x.iter().map(|&x| (x as f64*12.566).sin()).collect();
+
+    assert!(capacity >= 400);
+    assert_eq!(xicorf_in(&mut ws, &x, &y), xicorf(&x, &y));
+    assert_eq!(ws.capacity(), capacity);
+
+    // Long enough for the keys to be radix sorted in the workspace
+    let x: Vec<u32> = (0..3000).map(|i| (i*7919)%2999).collect();
+    let y: Vec<u32> = x.iter().map(|x| (x*x)%101).collect();
+
+    assert_eq!(xicor_in(&mut ws, &x, &y), xicor(&x, &y));
+
+    // Narrow keys never need the wide buffer, so it is not grown for them
+    assert!(ws.capacity() >= 3000);
+}
+
+#[test]
+fn test_radix_argsort() {
+    fn check<T: Ord>(arr: &[T]) {
+        // Short arrays are sorted by comparison and long ones by radix
+        for arr in [&arr[..1000], arr] {
+            let radix = radix::radix_argsort(arr).expect("type should be radix sortable");
+            let other: Vec<usize> = (0..arr.len()).map(|i| (i*i)%1009).collect();
+
+            assert_eq!(radix, xicor::argsort_by(arr, T::cmp));
+
+            // The rank quantities, with both narrow and wide partners
+            for (a, b) in [
+                (xicor::ranks(arr, &other), xicor::ranks_by(arr, &other, T::cmp, usize::cmp)),
+                (xicor::ranks(&other, arr), xicor::ranks_by(&other, arr, usize::cmp, T::cmp)),
+            ] {
+                assert_eq!((a.rs, a.lsum, a.distinct), (b.rs, b.lsum, b.distinct));
+            }
+        }
+    }
+
+    let ints: Vec<i64> = (0..70000).map(|i| (i*i*7919)%20011-10000).collect();
+
+    check(&ints);
+    check(&ints.iter().map(|&i| i as i8).collect::<Vec<_>>());
+    check(&ints.iter().map(|&i| i as u16).collect::<Vec<_>>());
+    check(&ints.iter().map(|&i| i as u32).collect::<Vec<_>>());
+    check(&ints.iter().map(|&i| i as isize).collect::<Vec<_>>());
+    check(&ints.iter().collect::<Vec<_>>());
+
+    // Signed zeros are tied and NaNs sort above infinity, as for OrderedFloat
+    let special = [0., -0., f64::NAN, -f64::NAN, f64::INFINITY, f64::NEG_INFINITY, -1e-300];
+    let floats: Vec<OrderedFloat<f64>> = ints.iter()
+        .map(|&i| OrderedFloat(i as f64/7.))
+        .chain(special.iter().cycle().take(3000).map(|&v| OrderedFloat(v)))
+        .collect();
+
+    check(&floats);
+    check(&floats.iter().map(|v| OrderedFloat(v.0 as f32)).collect::<Vec<_>>());
+
+    let strings: Vec<String> = ints.iter().map(|i| i.to_string()).collect();
+
+    assert!(radix::radix_argsort(&strings).is_none());
+}
+
+#[test]
+fn test_xicor_counting() {
+    let x: Vec<i16> = (0..2000).map(|i| ((i*i*31)%301-150) as i16).collect();
+    let y: Vec<u8> = (0..2000).map(|i| ((i*17)%11*(i%5)) as u8).collect();
+
+    assert_eq!(xicor_counting(&x, &y), xicor(&x, &y));
+    assert_eq!(xicor_counting(&y, &x), xicor(&y, &x));
+
+    // Wide ranges fall back to the comparison sort
+    let wide: Vec<i64> = (0..100).map(|i| i*(1 << 40)-(1 << 45)).collect();
+
+    assert_eq!(xicor_counting(&wide, &x[..100]), xicor(&wide, &x[..100]));
+    assert!(xicor_counting::<u8, u8>(&[], &[]).is_nan());
+    assert!(xicor_counting(&[1u8], &[2u8]).is_nan());
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_xicor_par() {
+    for n in [0, 1, 2, 10, 5000] {
+        let x: Vec<i32> = (0..n).map(|i| (i*i*31)%997).collect();
+        let y: Vec<f64> = x.iter().map(|&x| ((x%89) as f64).sin()).collect();
+        let s: Vec<String> = x.iter().map(|x| (x%50).to_string()).collect();
+
+        for (a, b) in [
+            (xicor_par(&x, as_ordered(&y)), xicor(&x, as_ordered(&y))),
+            (xicorf_par(&y, &y), xicorf(&y, &y)),
+            (xicor_par(&s, &x), xicor(&s, &x)),
+        ] {
+            assert!(a.to_bits() == b.to_bits() || (a.is_nan() && b.is_nan()), "{a} != {b}");
+        }
+    }
+}
+
+#[test]
+fn test_xicor_exact() {
+    let x: Vec<u32> = (0..3000).map(|i| (i*7919)%2999).collect();
+    let y: Vec<u32> = x.iter().map(|x| (x*x)%101).collect();
+    let xi = xicor_exact(&x, &y);
+
+    assert_eq!(xi.to_f64(), xicor(&x, &y));
+    assert_eq!(xi.to_f64(), xicor_counting(&x, &y));
+    assert_eq!(xi.to_f64(), xicor_in(&mut XiWorkspace::new(), &x, &y));
+
+    // The fraction equals that of the sums, and is in lowest terms
+    let ranks = ranks(&x, &y);
+    let rsum = rank_diff_sum(&ranks.rs) as i128;
+    let lsum = ranks.lsum as i128;
+    let (mut a, mut b) = (xi.numerator.unsigned_abs(), xi.denominator);
+
+    while b != 0 { (a, b) = (b, a%b); }
+
+    assert_eq!(xi.numerator*2*lsum, (2*lsum-3000*rsum)*xi.denominator as i128);
+    assert_eq!(a, 1);
+    assert_eq!(xicor_exact(&[1, 2, 3, 4], &[1, 2, 3, 4]), XiRatio { numerator: 2, denominator: 5 });
+    assert_eq!(xicor_exact(&[1, 2], &[3, 3]).denominator, 0);
+    assert!(xicor_exact::<u8, u8>(&[], &[]).to_f64().is_nan());
+}
+
+#[test]
+fn test_streaming_xi() {
+    use rand::Rng;
+
+    let mut rng = StdRng::seed_from_u64(17);
+    let mut stream = StreamingXi::new();
+    let mut pairs: Vec<(u8, u8)> = vec![];
+
+    assert!(stream.is_empty());
+    assert!(stream.value().is_nan());
+
+    // Small ranges give plenty of ties in both x and y
+    for _ in 0..2000 {
+        if !pairs.is_empty() && rng.gen_bool(0.4) {
+            let (x, y) = pairs[rng.gen_range(0..pairs.len())];
+            let i = pairs.iter().position(|&p| p == (x, y)).unwrap();
+
+            assert!(stream.remove(&x, &y));
+            pairs.remove(i);
+        } else {
+            let pair = (rng.gen_range(0..20), rng.gen_range(0..10));
+
+            stream.insert(pair.0, pair.1);
+            pairs.push(pair);
+        }
+
+        let (x, y): (Vec<u8>, Vec<u8>) = pairs.iter().copied().unzip();
+
+        assert_eq!(stream.len(), pairs.len());
+        assert_eq!(stream.ratio(), xicor_exact(&x, &y));
+    }
+
+    assert!(!stream.remove(&20, &0));
+}
+
+#[test]
+fn test_rolling_xicor() {
+    let x: Vec<u16> = (0..600).map(|i| ((i*7919)%601) as u16/8).collect();
+    let y: Vec<u16> = x.iter().map(|x| (x*x)%29).collect();
+    let xf: Vec<f64> = (0..600).map(|i| (i as f64*0.71).cos()).collect();
+    let yf: Vec<f64> = (0..600).map(|i| (i as f64*0.37).sin()).collect();
+
+    for (window, step) in [(50, 1), (100, 30), (40, 40), (30, 75), (600, 1), (601, 1)] {
+        let rolling = rolling_xicor(&x, &y, window, step, true);
+
+        assert_eq!(rolling.len(), (600+step).saturating_sub(window)/step);
+
+        for win in rolling {
+            let (xs, ys) = (&x[win.end-window..win.end], &y[win.end-window..win.end]);
+            let test = xicor_test(xs, ys);
+
+            assert_eq!(win.xi, xicor(xs, ys));
+            assert_eq!(win.p_value, Some(test.p_value));
+        }
+
+        // Without ties in y the p-values use the continuous variance
+        for win in rolling_xicorf(&xf, &yf, window, step, true) {
+            let (xs, ys) = (&xf[win.end-window..win.end], &yf[win.end-window..win.end]);
+            let test = xicorf_test(xs, ys);
+
+            assert_eq!(test.null_variance, NullVariance::Continuous);
+            assert_eq!((win.xi, win.p_value), (xicorf(xs, ys), Some(test.p_value)));
+        }
+    }
+
+    assert_eq!(rolling_xicor(&x, &y, 0, 300, false).iter().map(|w| w.end).collect::<Vec<_>>(), [0, 300, 600]);
+}
+
+#[test]
+fn test_xi_summary() {
+    let x: Vec<u16> = (0..1000).map(|i| ((i*7919)%1009) as u16/10).collect();
+    let y: Vec<u16> = x.iter().map(|x| (x*x)%31).collect();
+    let summary = |r: std::ops::Range<usize>| XiSummary::new(&x[r.clone()], &y[r]);
+
+    // Merging in any tree shape gives the same result, provided the order of
+    // the partitions is kept
+    let mut left = summary(0..100);
+    let mut right = summary(400..1000);
+
+    left.merge(summary(100..400));
+    right.merge(XiSummary::default());
+    left.merge(right);
+
+    assert_eq!(left.ratio(), xicor_exact(&x, &y));
+    assert_eq!(left, summary(0..1000));
+
+    let mut tiny = XiSummary::newf(&[0.5, 0.1], &[1., 2.]);
+
+    tiny.merge(XiSummary::newf(&[0.1, 0.9], &[3., 0.]));
+    assert_eq!(tiny.value(), xicorf(&[0.5, 0.1, 0.1, 0.9], &[1., 2., 3., 0.]));
+}
+
+#[test]
+fn test_xi_compressed_summary() {
+    let x: Vec<u16> = (0..20000).map(|i| (i/4) as u16).collect();
+    let y: Vec<u16> = x.iter().map(|&x| ((x as u32*x as u32)%257) as u16+x/50).collect();
+    let exact = xicor(&x, &y);
+    let summary = |r: std::ops::Range<usize>, k| {
+        XiCompressedSummary::new(&x[r.clone()], &y[r], k)
+    };
+
+    // Small enough partitions are summarised exactly, in any tree shape
+    let mut left = summary(0..3000, 1<<15);
+    let mut right = summary(9000..20000, 1<<15);
+
+    left.merge(summary(3000..9000, 1<<15));
+    right.merge(XiCompressedSummary::new(&[], &[], 1<<15));
+    left.merge(right);
+
+    let estimate = left.estimate();
+
+    assert_eq!((estimate.xi, estimate.lower, estimate.upper), (exact, exact, exact));
+    assert_eq!(left.len(), 20000);
+
+    // Compressed partitions which follow each other in x stay within bounds
+    let mut total = summary(0..500, 128);
+
+    for start in (500..20000).step_by(1500) {
+        total.merge(summary(start..(start+1500).min(20000), 128));
+    }
+
+    let estimate = total.estimate();
+
+    assert!(estimate.lower <= exact && exact <= estimate.upper, "{estimate:?}");
+    assert!((estimate.xi-exact).abs() < 0.05);
+    assert_eq!(estimate.late, 0);
+
+    // Overlapping partitions are bounded through their late pairs
+    let mut total = summary(0..15000, 128);
+
+    total.merge(summary(5000..20000, 128));
+
+    let (xs, ys) = ([&x[..15000], &x[5000..]].concat(), [&y[..15000], &y[5000..]].concat());
+    let exact = xicor(&xs, &ys);
+    let estimate = total.estimate();
+
+    assert!(estimate.lower <= exact && exact <= estimate.upper, "{estimate:?}");
+    assert!(estimate.late >= 10000);
+
+    let tiny = XiCompressedSummary::newf(&[0.5, 0.1], &[1., 2.], 16);
+
+    assert!(!tiny.is_empty());
+    assert_eq!(tiny.estimate().xi, xicorf(&[0.5, 0.1], &[1., 2.]));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_xi_summary_serde() {
+    let x = [3.5, 1.25, -0.75, 2.];
+    let y = [1., 4., 2., 0.];
+    let summary = XiSummary::newf(&x, &y);
+    let json = serde_json::to_string(&summary).unwrap();
+    let mut parsed: XiSummary<OrderedFloat<f64>, OrderedFloat<f64>> = serde_json::from_str(&json).unwrap();
+
+    parsed.merge(XiSummary::newf(&[0.5], &[3.]));
+    assert_eq!(parsed.value(), xicorf(&[3.5, 1.25, -0.75, 2., 0.5], &[1., 4., 2., 0., 3.]));
+
+    // Summaries which this crate could not have built are rejected
+    let parse = |json| serde_json::from_str::<XiSummary<i32, i32>>(json);
+
+    assert!(parse(r#"{"x":[1,2],"y":[3,4,5]}"#).unwrap_err().to_string().contains("length"));
+    assert!(parse(r#"{"x":[2,1],"y":[3,4]}"#).is_err());
+    assert!(parse(r#"{"x":[1,1,2],"y":[3,4,5]}"#).is_ok());
+
+    let x: Vec<u32> = (0..5000).collect();
+    let y: Vec<u32> = x.iter().map(|x| (x*x)%1009).collect();
+    let summary = XiCompressedSummary::new(&x[..3000], &y[..3000], 64);
+    let json = serde_json::to_value(&summary).unwrap();
+    let mut parsed: XiCompressedSummary<u32, u32> = serde_json::from_value(json.clone()).unwrap();
+
+    parsed.merge(XiCompressedSummary::new(&x[3000..], &y[3000..], 64));
+    assert_eq!(parsed.len(), 5000);
+    assert_eq!(parsed.estimate(), {
+        let mut summary = summary;
+
+        summary.merge(XiCompressedSummary::new(&x[3000..], &y[3000..], 64));
+        summary.estimate()
+    });
+
+    for (field, value) in [("pushed", 2999), ("late", 3001)] {
+        let mut json = json.clone();
+
+        json["sketch"][field] = value.into();
+        assert!(serde_json::from_value::<XiCompressedSummary<u32, u32>>(json).is_err());
+    }
+
+    let mut json = json;
+
+    json["sketch"]["ys"]["len"] = 2999.into();
+    assert!(serde_json::from_value::<XiCompressedSummary<u32, u32>>(json).is_err());
+}
+
+#[test]
+fn test_xi_sketch() {
+    let x: Vec<u32> = (0..30000).map(|i| i/3).collect();
+    let y: Vec<u32> = x.iter().map(|x| (x*x)%5003+x/7).collect();
+    let exact = xicor(&x, &y);
+
+    // Without compaction or late pairs, the sketch is exact
+    let mut sketch = XiSketch::new(1<<16, 0);
+
+    for (&x, &y) in x.iter().zip(&y) { sketch.push(x, y); }
+
+    let estimate = sketch.finish();
+
+    assert_eq!((estimate.xi, estimate.lower, estimate.upper), (exact, exact, exact));
+    assert_eq!((estimate.n, estimate.late), (30000, 0));
+
+    // Shuffle x locally, so that a large enough buffer can restore its order
+    let perm: Vec<usize> = (0..30000).map(|i| i^((i*7)%16)).collect();
+    let (xs, ys): (Vec<u32>, Vec<u32>) = perm.iter().map(|&i| (x[i], y[i])).unzip();
+    let exact = xicor(&xs, &ys);
+
+    for (k, buffer) in [(256, 32), (1000, 4), (4096, 0), (64, 64)] {
+        let mut sketch = XiSketch::new(k, buffer);
+
+        for (&x, &y) in xs.iter().zip(&ys) { sketch.push(x, y); }
+
+        let estimate = sketch.estimate();
+
+        assert!(estimate.lower <= exact && exact <= estimate.upper, "{k} {buffer}: {estimate:?}");
+        assert!(estimate.lower <= estimate.xi && estimate.xi <= estimate.upper);
+        assert_eq!(estimate.late == 0, buffer >= 32);
+        assert_eq!(sketch.finish(), estimate);
+
+        if buffer >= 32 { assert!((estimate.xi-exact).abs() < 0.05); }
+    }
+
+    // Chunks which follow each other in x merge without late pairs, while
+    // overlapping ones are bounded through the sketches of x
+    let exact = xicor(&x, &y);
+
+    for (chunk, k) in [(3000, 256), (7000, 64), (30000, 1<<16)] {
+        let mut total = XiSketch::new(k, 32);
+
+        for (x, y) in x.chunks(chunk).zip(y.chunks(chunk)) {
+            let mut sketch = XiSketch::new(k, 32);
+
+            for (&x, &y) in x.iter().zip(y) { sketch.push(x, y); }
+
+            total.merge(sketch);
+        }
+
+        let estimate = total.finish();
+
+        assert!(estimate.lower <= exact && exact <= estimate.upper, "{chunk}: {estimate:?}");
+        assert_eq!(estimate.n, 30000);
+        assert_eq!(estimate.late, 0);
+
+        if k == 1<<16 { assert_eq!(estimate.xi, exact); }
+    }
+
+    let mut front = XiSketch::new(256, 8);
+    let mut back = XiSketch::new(256, 8);
+
+    for (&x, &y) in xs.iter().zip(&ys).step_by(2) { front.push(x, y); }
+    for (&x, &y) in xs.iter().zip(&ys).skip(1).step_by(2) { back.push(x, y); }
+
+    let interleaved: Vec<(u32, u32)> = xs.iter().zip(&ys).step_by(2)
+        .chain(xs.iter().zip(&ys).skip(1).step_by(2))
+        .map(|(&x, &y)| (x, y))
+        .collect();
+    let (xi, yi): (Vec<u32>, Vec<u32>) = interleaved.into_iter().unzip();
+    let exact = xicor(&xi, &yi);
+
+    front.merge(back);
+
+    let estimate = front.finish();
+
+    assert!(estimate.lower <= exact && exact <= estimate.upper, "{estimate:?}");
+    assert!(estimate.late >= 14900 && estimate.late <= 15000);
+
+    let mut sketch = XiSketch::new(8, 2);
+
+    assert!(sketch.is_empty());
+    sketch.push(OrderedFloat(1.), OrderedFloat(f64::NAN));
+    assert!(sketch.estimate().xi.is_nan());
+}
+
+#[test]
+fn test_compactor() {
+    let k = 64;
+    let values: Vec<u32> = (0..200000).map(|i| (i*7919)%100003).collect();
+    let mut whole = compactor::Compactor::new(k);
+    let mut halves = [compactor::Compactor::new(k), compactor::Compactor::new(k)];
+
+    for (i, &v) in values.iter().enumerate() {
+        whole.push(v);
+        halves[i%2].push(v);
+    }
+
+    let [mut merged, other] = halves;
+
+    merged.merge(other);
+
+    for sketch in [&whole, &merged] {
+        let weighted = sketch.weighted();
+
+        // Memory is bounded however many values are pushed
+        assert!(weighted.len() <= 3*k+128, "{}", weighted.len());
+        assert_eq!(weighted.iter().map(|&(_, w)| w).sum::<u128>(), 200000);
+        assert_eq!(sketch.len(), 200000);
+
+        for bound in [0, 1000, 50000, 99999, 200000] {
+            let exact = values.iter().filter(|&&v| v < bound).count() as i128;
+            let estimate = sketch.count_below(&bound) as i128;
+
+            assert!((estimate-exact).abs() <= sketch.error() as i128);
+        }
+    }
+}
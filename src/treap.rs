@@ -0,0 +1,233 @@
+use std::cmp::Ordering;
+
+
+
+// An order-statistic multiset, implemented as a treap with one node per
+// distinct value. Every subtree records its total count and the sum of the
+// squares of the counts of its distinct values, so the number of values below
+// any bound, and the sum of squared counts below it, are found in O(log n).
+pub(super) struct Multiset<K> {
+    root: Link<K>,
+    // State of the splitmix64 generator for node priorities, which is seeded
+    // identically for every multiset so that behaviour is reproducible
+    state: u64,
+}
+
+type Link<K> = Option<Box<Node<K>>>;
+
+struct Node<K> {
+    key: K,
+    count: usize,
+    priority: u64,
+    // Total count of every value in the subtree
+    size: usize,
+    // Sum of the squared counts of the distinct values in the subtree
+    squares: u128,
+    left: Link<K>,
+    right: Link<K>,
+}
+
+impl<K> Node<K> {
+    fn new(key: K, priority: u64) -> Self {
+        Self { key, count: 1, priority, size: 1, squares: 1, left: None, right: None }
+    }
+
+    fn update(&mut self) {
+        let c = self.count;
+
+        self.size = size(&self.left)+c+size(&self.right);
+        self.squares = squares(&self.left)+(c*c) as u128+squares(&self.right);
+    }
+}
+
+fn size<K>(link: &Link<K>) -> usize {
+    link.as_ref().map_or(0, |node| node.size)
+}
+
+fn squares<K>(link: &Link<K>) -> u128 {
+    link.as_ref().map_or(0, |node| node.squares)
+}
+
+impl<K: Ord> Multiset<K> {
+    pub(super) fn new() -> Self {
+        Self { root: None, state: 0 }
+    }
+
+    // The total number of values, counting repeats.
+    pub(super) fn len(&self) -> usize {
+        size(&self.root)
+    }
+
+    // The sum of the squared counts of every distinct value.
+    pub(super) fn squares(&self) -> u128 {
+        squares(&self.root)
+    }
+
+    pub(super) fn insert(&mut self, key: K) {
+        let priority = self.next_priority();
+
+        self.root = Some(insert(self.root.take(), key, priority));
+    }
+
+    // Remove one copy of the key, returning whether there was one to remove.
+    pub(super) fn remove(&mut self, key: &K) -> bool {
+        let mut removed = false;
+
+        self.root = remove(self.root.take(), key, &mut removed);
+        removed
+    }
+
+    // The number of values less than the key.
+    pub(super) fn count_lt(&self, key: &K) -> usize {
+        self.below(key, false).0
+    }
+
+    // The number of values less than or equal to the key, along with the sum
+    // of the squared counts of the distinct values less than or equal to it.
+    pub(super) fn below_le(&self, key: &K) -> (usize, u128) {
+        self.below(key, true)
+    }
+
+    // The count of every distinct value, in ascending order of value.
+    pub(super) fn counts(&self) -> Vec<usize> {
+        let mut counts = vec![];
+        let mut stack = vec![];
+        let mut link = &self.root;
+
+        loop {
+            while let Some(node) = link {
+                stack.push(node);
+                link = &node.left;
+            }
+
+            let Some(node) = stack.pop() else { break };
+
+            counts.push(node.count);
+            link = &node.right;
+        }
+
+        counts
+    }
+
+    fn below(&self, key: &K, inclusive: bool) -> (usize, u128) {
+        let mut link = &self.root;
+        let mut count = 0;
+        let mut sq = 0;
+
+        while let Some(node) = link {
+            let goes_right = match key.cmp(&node.key) {
+                Ordering::Less => false,
+                Ordering::Equal => inclusive,
+                Ordering::Greater => true,
+            };
+
+            if goes_right {
+                count += size(&node.left)+node.count;
+                sq += squares(&node.left)+(node.count*node.count) as u128;
+                link = &node.right;
+            } else {
+                link = &node.left;
+            }
+        }
+
+        (count, sq)
+    }
+
+    fn next_priority(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+
+        let mut z = self.state;
+
+        z = (z^(z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z^(z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z^(z >> 31)
+    }
+}
+
+fn insert<K: Ord>(link: Link<K>, key: K, priority: u64) -> Box<Node<K>> {
+    let Some(mut node) = link else {
+        return Box::new(Node::new(key, priority));
+    };
+
+    match key.cmp(&node.key) {
+        Ordering::Equal => node.count += 1,
+        Ordering::Less => {
+            let child = insert(node.left.take(), key, priority);
+
+            node.left = Some(child);
+
+            if node.left.as_ref().unwrap().priority > node.priority {
+                node = rotate_right(node);
+            }
+        },
+        Ordering::Greater => {
+            let child = insert(node.right.take(), key, priority);
+
+            node.right = Some(child);
+
+            if node.right.as_ref().unwrap().priority > node.priority {
+                node = rotate_left(node);
+            }
+        },
+    }
+
+    node.update();
+    node
+}
+
+fn remove<K: Ord>(link: Link<K>, key: &K, removed: &mut bool) -> Link<K> {
+    let mut node = link?;
+
+    match key.cmp(&node.key) {
+        Ordering::Less => node.left = remove(node.left.take(), key, removed),
+        Ordering::Greater => node.right = remove(node.right.take(), key, removed),
+        Ordering::Equal => {
+            *removed = true;
+            node.count -= 1;
+
+            if node.count == 0 {
+                return merge(node.left.take(), node.right.take());
+            }
+        },
+    }
+
+    node.update();
+    Some(node)
+}
+
+// Join two treaps, where every key in the first is less than every key in the
+// second.
+fn merge<K>(left: Link<K>, right: Link<K>) -> Link<K> {
+    match (left, right) {
+        (None, link) | (link, None) => link,
+        (Some(mut l), Some(mut r)) => {
+            if l.priority > r.priority {
+                l.right = merge(l.right.take(), Some(r));
+                l.update();
+                Some(l)
+            } else {
+                r.left = merge(Some(l), r.left.take());
+                r.update();
+                Some(r)
+            }
+        },
+    }
+}
+
+fn rotate_right<K>(mut node: Box<Node<K>>) -> Box<Node<K>> {
+    let mut left = node.left.take().unwrap();
+
+    node.left = left.right.take();
+    node.update();
+    left.right = Some(node);
+    left
+}
+
+fn rotate_left<K>(mut node: Box<Node<K>>) -> Box<Node<K>> {
+    let mut right = node.right.take().unwrap();
+
+    node.right = right.left.take();
+    node.update();
+    right.left = Some(node);
+    right
+}
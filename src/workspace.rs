@@ -0,0 +1,147 @@
+use crate::radix::{radix_ranks, RadixBuffers};
+use crate::xicor::{as_ordered, rank_diff_sum, XiRatio};
+use num_traits::float::FloatCore;
+
+
+
+/// Reusable buffers for computing xi without allocating.
+///
+/// [`xicor`] allocates several vectors of length `n` on every call. When xi is
+/// computed repeatedly, for example in a real-time loop, those allocations can
+/// dominate the running time for moderate `n`. An `XiWorkspace` owns the
+/// buffers instead, growing them as needed, so that once it has seen the
+/// largest input no further allocation takes place. Inputs of different
+/// lengths and types can share the same workspace.
+///
+/// [`xicor`]: crate::xicor
+///
+/// # Example
+///
+/// ```
+/// use xicor::{xicor, xicor_in, XiWorkspace};
+///
+/// let mut ws = XiWorkspace::with_capacity(1000);
+///
+/// for n in [10, 1000, 500] {
+///     let x: Vec<u32> = (0..n).collect();
+///     let y: Vec<u32> = x.iter().map(|x| (x*x)%97).collect();
+///
+///     assert_eq!(xicor_in(&mut ws, &x, &y), xicor(&x, &y));
+/// }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct XiWorkspace {
+    x_order: Vec<usize>,
+    y_order: Vec<usize>,
+    rs: Vec<usize>,
+    ls: Vec<usize>,
+    radix: RadixBuffers,
+}
+
+impl XiWorkspace {
+    /// Create an empty workspace, which allocates on first use.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a workspace that can handle up to `n` pairs without allocating.
+    pub fn with_capacity(n: usize) -> Self {
+        Self {
+            x_order: Vec::with_capacity(n),
+            y_order: Vec::with_capacity(n),
+            rs: Vec::with_capacity(n),
+            ls: Vec::with_capacity(n),
+            radix: RadixBuffers::with_capacity(n),
+        }
+    }
+
+    /// The largest number of pairs that can be handled without allocating,
+    /// if they have the same types as the pairs last handled.
+    pub fn capacity(&self) -> usize {
+        // Radix keys are ranked straight into rs, without the index buffers
+        if let Some(radix) = self.radix.capacity() {
+            return radix.min(self.rs.capacity());
+        }
+
+        [&self.x_order, &self.y_order, &self.rs, &self.ls].iter()
+            .map(|buf| buf.capacity())
+            .min()
+            .unwrap()
+    }
+
+    // Size every buffer for n pairs, reusing the existing allocations.
+    fn reset(&mut self, n: usize) {
+        self.x_order.clear();
+        self.x_order.extend(0..n);
+        self.y_order.clear();
+        self.y_order.extend(0..n);
+        self.rs.resize(n, 0);
+        self.ls.resize(n, 0);
+    }
+}
+
+/// Calculate the xi-correlation of two floating-point sequences using the
+/// buffers of a workspace.
+///
+/// See [`xicor_in`] for details.
+pub fn xicorf_in<FX, FY>(ws: &mut XiWorkspace, x: &[FX], y: &[FY]) -> f64
+where
+    FX: FloatCore,
+    FY: FloatCore,
+{
+    xicor_in(ws, as_ordered(x), as_ordered(y))
+}
+
+/// Calculate the xi-correlation of two sequences whose values are orderable
+/// (they implement [`Ord`]) using the buffers of a workspace.
+///
+/// The result is exactly that of [`xicor`], but no memory is allocated once
+/// the workspace has grown to the size of the input. Rather than arranging
+/// copies of the data, only indices into `x` and `y` are sorted, and the ranks
+/// are accumulated as integers. Primitive integers and floats are instead
+/// ranked by sorting keys packed with their indices, exactly as [`xicor`]
+/// does, with the keys kept in the workspace too.
+///
+/// [`xicor`]: crate::xicor
+///
+/// # Panics
+///
+/// If `x` and `y` differ in length.
+pub fn xicor_in<X: Ord, Y: Ord>(ws: &mut XiWorkspace, x: &[X], y: &[Y]) -> f64 {
+    assert!(x.len() == y.len(), "x and y must have the same length");
+
+    let n = x.len();
+
+    if let Some((lsum, _)) = radix_ranks(x, y, &mut ws.radix, &mut ws.rs) {
+        return XiRatio::new(n, rank_diff_sum(&ws.rs), lsum).to_f64();
+    }
+
+    ws.reset(n);
+    ws.x_order.sort_unstable_by(|&i, &j| x[i].cmp(&x[j]).then(i.cmp(&j)));
+    ws.y_order.sort_unstable_by(|&i, &j| y[i].cmp(&y[j]));
+
+    // Within each run of equal y values, r is the position of the end of the
+    // run and l is the number of values from its start onwards
+    let mut start = 0;
+
+    while start < n {
+        let first = &y[ws.y_order[start]];
+        let len = ws.y_order[start..].partition_point(|&i| y[i] == *first);
+
+        for &i in &ws.y_order[start..start+len] {
+            ws.rs[i] = start+len;
+            ws.ls[i] = n-start;
+        }
+
+        start += len;
+    }
+
+    let rsum = ws.x_order.windows(2)
+        .map(|win| ws.rs[win[0]].abs_diff(ws.rs[win[1]]) as u128)
+        .sum::<u128>();
+    let lsum = ws.ls.iter()
+        .map(|&l| l as u128*(n-l) as u128)
+        .sum::<u128>();
+
+    XiRatio::new(n, rsum, lsum).to_f64()
+}
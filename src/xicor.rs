@@ -1,8 +1,74 @@
+use crate::error::XiError;
+use crate::radix::{radix_argsort, radix_ranks, RadixBuffers};
+use crate::ranking::XRanking;
 use ordered_float::OrderedFloat;
 use num_traits::float::FloatCore;
+use rand::SeedableRng;
+use rand::seq::SliceRandom;
+use rand_chacha::ChaCha8Rng;
+use std::cmp::Ordering;
 
 
 
+/// How to order pairs whose x values are tied.
+///
+/// Xi depends on the order of the pairs when sorted by x, which is ambiguous
+/// when x values are repeated. Chatterjee's definition breaks such ties
+/// uniformly at random.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TiePolicy {
+    /// Keep pairs with tied x values in the order they appear in the input.
+    /// This is what [`xicor`] and the other functions without a tie policy
+    /// use.
+    #[default]
+    Stable,
+    /// Shuffle pairs with tied x values uniformly at random, as in the paper.
+    /// The shuffle is driven by a ChaCha8 generator seeded with the given
+    /// value, so results are reproducible across platforms and releases.
+    Random(u64),
+    /// Compute the exact expectation of xi over every possible ordering of
+    /// the pairs with tied x values. This is the average of the xi values
+    /// given by `Random` over all seeds, computed without any randomness.
+    Expected,
+}
+
+/// The xi-correlation as an exact fraction in lowest terms.
+///
+/// Both sums in the formula for xi are integers, so xi is always rational.
+/// Under the stable and random tie policies, the xi functions convert this
+/// fraction with [`to_f64`](Self::to_f64), so they agree exactly however the
+/// sums were accumulated. The expected tie policy and the normalised
+/// coefficient are computed in floating point instead, and the sketch
+/// estimates convert a ratio of estimated sums. The conversion divides the
+/// numerator and denominator as floats, so it is correctly rounded while both
+/// are below 2^53, and may otherwise be off by up to three roundings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct XiRatio {
+    /// The numerator, which is negative when xi is.
+    pub numerator: i128,
+    /// The denominator, which is zero when xi is undefined.
+    pub denominator: u128,
+}
+
+impl XiRatio {
+    // Build the ratio 1 - n*rsum/(2*lsum) = (2*lsum - n*rsum)/(2*lsum).
+    pub(super) fn new(n: usize, rsum: u128, lsum: u128) -> Self {
+        let numerator = 2*lsum as i128-(n as u128*rsum) as i128;
+        let denominator = 2*lsum;
+        let divisor = gcd(numerator.unsigned_abs(), denominator);
+
+        match divisor {
+            0 => Self { numerator, denominator },
+            d => Self { numerator: numerator/d as i128, denominator: denominator/d },
+        }
+    }
+
+    /// The value of the fraction, which is NaN if the denominator is zero.
+    pub fn to_f64(self) -> f64 {
+        self.numerator as f64/self.denominator as f64
+    }
+}
+
 /// Calculate the normalised xi-correlation of two floating-point sequences.
 ///
 /// See [`xicor_norm`] for details of normalisation.
@@ -21,11 +87,8 @@ use num_traits::float::FloatCore;
 ///
 /// Note that this is exactly the same data used in the example for [`xicorf`],
 /// but here the result is actually 1.
-pub fn xicorf_norm<F: FloatCore>(x: &[F], y: &[F]) -> f64 {
-    let n = x.len() as f64;
-    let lim = (n-2.)/(n+1.);
-
-    xicorf(x, y)/lim
+pub fn xicorf_norm<FX: FloatCore, FY: FloatCore>(x: &[FX], y: &[FY]) -> f64 {
+    xicor_norm(as_ordered(x), as_ordered(y))
 }
 
 /// Calculate the normalised xi-correlation of two sequences whose values are
@@ -52,7 +115,7 @@ pub fn xicorf_norm<F: FloatCore>(x: &[F], y: &[F]) -> f64 {
 ///
 /// Note that this is exactly the same data used in the example for [`xicor`],
 /// but here the result is actually 1.
-pub fn xicor_norm<T: Ord + Copy>(x: &[T], y: &[T]) -> f64 {
+pub fn xicor_norm<X: Ord, Y: Ord>(x: &[X], y: &[Y]) -> f64 {
     let n = x.len() as f64;
     let lim = (n-2.)/(n+1.);
 
@@ -61,8 +124,14 @@ pub fn xicor_norm<T: Ord + Copy>(x: &[T], y: &[T]) -> f64 {
 
 /// Calculate the xi-correlation of two floating-point sequences.
 ///
-/// This is a thin wrapper around [`xicor`] that transmutes slices of floats
-/// into slices of [`OrderedFloat`], which implements the necessary [`Ord`].
+/// This transmutes slices of floats into slices of [`OrderedFloat`], which
+/// implements the necessary [`Ord`]. The two sequences may use different float
+/// types.
+///
+/// Any NaN values are silently treated as larger than every other value. Use
+/// [`xicorf_with_nan`] to choose how NaN is handled explicitly.
+///
+/// [`xicorf_with_nan`]: crate::xicorf_with_nan
 ///
 /// # Example
 ///
@@ -75,17 +144,224 @@ pub fn xicor_norm<T: Ord + Copy>(x: &[T], y: &[T]) -> f64 {
 ///
 /// assert_eq!(xi, 0.9375);
 /// ```
-pub fn xicorf<F: FloatCore>(x: &[F], y: &[F]) -> f64 {
-    // This is safe because OrderedFloat has transparent representation
-    let x: &[OrderedFloat<F>] = unsafe { std::mem::transmute(x) };
-    let y: &[OrderedFloat<F>] = unsafe { std::mem::transmute(y) };
+pub fn xicorf<FX: FloatCore, FY: FloatCore>(x: &[FX], y: &[FY]) -> f64 {
+    xicor(as_ordered(x), as_ordered(y))
+}
+
+/// Calculate the xi-correlation of a floating-point x sequence and a y
+/// sequence whose values are orderable (they implement [`Ord`]).
+///
+/// # Example
+///
+/// ```
+/// use xicor::xicorf_x;
+///
+/// let x: Vec<f64> = (0..47).map(|i| i as f64/10.).collect();
+/// let y: Vec<u64> = (0..47).map(|i| i*i).collect();
+/// let xi = xicorf_x(&x, &y);
+///
+/// assert_eq!(xi, 0.9375);
+/// ```
+pub fn xicorf_x<F: FloatCore, Y: Ord>(x: &[F], y: &[Y]) -> f64 {
+    xicor(as_ordered(x), y)
+}
+
+/// Calculate the xi-correlation of an x sequence whose values are orderable
+/// (they implement [`Ord`]) and a floating-point y sequence.
+///
+/// # Example
+///
+/// ```
+/// use xicor::xicorf_y;
+///
+/// let x: Vec<i64> = (0..47).collect();
+/// let y: Vec<f32> = x.iter().map(|&x| (x*x) as f32).collect();
+/// let xi = xicorf_y(&x, &y);
+///
+/// assert_eq!(xi, 0.9375);
+/// ```
+pub fn xicorf_y<X: Ord, F: FloatCore>(x: &[X], y: &[F]) -> f64 {
+    xicor(x, as_ordered(y))
+}
 
-    xicor(x, y)
+/// Calculate the xi-correlation of two floating-point sequences, with an
+/// explicit policy for ordering tied x values.
+///
+/// See [`xicor_ties`] for details.
+///
+/// # Example
+///
+/// ```
+/// use xicor::{xicorf_ties, TiePolicy};
+///
+/// let x = [0., 0., 1., 1., 2., 2.];
+/// let y = [0.5, 2.5, 1.5, 3.5, 4.5, 5.5];
+///
+/// let stable = xicorf_ties(&x, &y, TiePolicy::Stable);
+/// let expected = xicorf_ties(&x, &y, TiePolicy::Expected);
+///
+/// assert!((stable-0.4).abs() < 1e-12);
+/// assert!((expected-8./35.).abs() < 1e-12);
+/// ```
+pub fn xicorf_ties<FX, FY>(x: &[FX], y: &[FY], ties: TiePolicy) -> f64
+where
+    FX: FloatCore,
+    FY: FloatCore,
+{
+    xicor_ties(as_ordered(x), as_ordered(y), ties)
+}
+
+/// Calculate the xi-correlation of two sequences whose values are orderable
+/// (they implement [`Ord`]), with an explicit policy for ordering tied x
+/// values.
+///
+/// Xi depends on the order in which pairs with tied x values are visited, so
+/// for discretised x data the policy matters. [`TiePolicy::Random`] follows
+/// the paper, breaking ties uniformly at random but reproducibly.
+/// [`TiePolicy::Expected`] removes the randomness altogether by averaging over
+/// every ordering of the ties, which is computed exactly in `O(n log n)` time.
+///
+/// # Example
+///
+/// ```
+/// use xicor::{xicor, xicor_ties, TiePolicy};
+///
+/// let x = [0, 0, 1, 1, 2, 2];
+/// let y = [0, 2, 1, 3, 4, 5];
+///
+/// assert_eq!(xicor_ties(&x, &y, TiePolicy::Stable), xicor(&x, &y));
+/// assert_eq!(
+///     xicor_ties(&x, &y, TiePolicy::Random(7)),
+///     xicor_ties(&x, &y, TiePolicy::Random(7)),
+/// );
+/// ```
+pub fn xicor_ties<X, Y>(x: &[X], y: &[Y], ties: TiePolicy) -> f64
+where
+    X: Ord,
+    Y: Ord,
+{
+    assert!(x.len() == y.len(), "x and y must have the same length");
+
+    XRanking::new(x, ties).xi_with(y)
+}
+
+/// Calculate the xi-correlation of two floating-point sequences, returning an
+/// error instead of panicking or producing NaN for unsuitable data.
+///
+/// In addition to the errors reported by [`try_xicor`], this returns
+/// [`XiError::NaN`] if any value is NaN.
+///
+/// # Example
+///
+/// ```
+/// use xicor::{try_xicorf, XiError};
+///
+/// assert_eq!(try_xicorf(&[1., 2., 3.], &[1., f64::NAN, 2.]), Err(XiError::NaN));
+/// assert_eq!(try_xicorf(&[1., 2., 3.], &[1., 3., 2.]), Ok(-0.125));
+/// ```
+pub fn try_xicorf<FX, FY>(x: &[FX], y: &[FY]) -> Result<f64, XiError>
+where
+    FX: FloatCore,
+    FY: FloatCore,
+{
+    check_lengths(x, y)?;
+
+    if x.iter().any(|v| v.is_nan()) || y.iter().any(|v| v.is_nan()) {
+        return Err(XiError::NaN);
+    }
+
+    try_xicor(as_ordered(x), as_ordered(y))
+}
+
+/// Calculate the xi-correlation of two sequences whose values are orderable
+/// (they implement [`Ord`]), returning an error instead of panicking or
+/// producing NaN for unsuitable data.
+///
+/// Errors are returned if `x` and `y` differ in length, if there are fewer than
+/// 2 pairs, or if every y value is the same. In all other cases the result is
+/// exactly that of [`xicor`].
+///
+/// # Example
+///
+/// ```
+/// use xicor::{try_xicor, XiError};
+///
+/// assert_eq!(
+///     try_xicor(&[1, 2, 3], &[4, 5]),
+///     Err(XiError::LengthMismatch { x: 3, y: 2 }),
+/// );
+/// assert_eq!(try_xicor(&[1, 2, 3], &[7, 7, 7]), Err(XiError::ConstantY));
+/// assert_eq!(try_xicor(&[1, 2, 3], &[1, 3, 2]), Ok(-0.125));
+/// ```
+pub fn try_xicor<X: Ord, Y: Ord>(x: &[X], y: &[Y]) -> Result<f64, XiError> {
+    check_lengths(x, y)?;
+
+    ranks(x, y).try_xi()
+}
+
+/// Calculate the xi-correlation of two floating-point sequences as an exact
+/// fraction.
+///
+/// See [`xicor_exact`] for details.
+///
+/// # Example
+///
+/// ```
+/// use xicor::{xicorf, xicorf_exact};
+///
+/// let x = [0.1, 0.5, 0.2, 0.8, 0.6, 0.9];
+/// let y = [1.0, 2.5, 1.5, 4.0, 3.0, 4.5];
+/// let xi = xicorf_exact(&x, &y);
+///
+/// assert_eq!((xi.numerator, xi.denominator), (4, 7));
+/// assert_eq!(xi.to_f64(), xicorf(&x, &y));
+/// ```
+pub fn xicorf_exact<FX: FloatCore, FY: FloatCore>(x: &[FX], y: &[FY]) -> XiRatio {
+    xicor_exact(as_ordered(x), as_ordered(y))
+}
+
+/// Calculate the xi-correlation of two sequences whose values are orderable
+/// (they implement [`Ord`]) as an exact fraction.
+///
+/// The sums of `|r_i - r_{i+1}|` and `l_i(n - l_i)` are accumulated exactly as
+/// integers, so the result does not depend on summation order and stays exact
+/// for any `n`, whereas floating-point sums lose precision for `n` beyond
+/// about 10^8. [`xicor`] returns exactly [`XiRatio::to_f64`] of this.
+///
+/// # Panics
+///
+/// If `x` and `y` differ in length.
+///
+/// # Example
+///
+/// ```
+/// use xicor::{xicor, xicor_exact, XiRatio};
+///
+/// let xi = xicor_exact(&[1, 2, 3], &[1, 3, 2]);
+///
+/// assert_eq!(xi, XiRatio { numerator: -1, denominator: 8 });
+/// assert_eq!(xi.to_f64(), xicor(&[1, 2, 3], &[1, 3, 2]));
+/// ```
+pub fn xicor_exact<X: Ord, Y: Ord>(x: &[X], y: &[Y]) -> XiRatio {
+    ranks(x, y).ratio()
 }
 
 /// Calculate the xi-correlation of two sequences whose values are orderable
 /// (they implement [`Ord`]).
 ///
+/// The x and y values need not be of the same type, as they are never compared
+/// with each other. For example, timestamps can be correlated with readings,
+/// or string categories with integers.
+///
+/// Pairs with tied x values are kept in the order they appear in the input.
+/// Use [`xicor_ties`] for other ways of breaking ties.
+///
+/// # Panics
+///
+/// If `x` and `y` differ in length. Note also that xi is undefined, and NaN is
+/// returned, if there are fewer than 2 pairs or every y value is the same. See
+/// [`try_xicor`] for a version which reports these problems as errors.
+///
 /// # Example
 ///
 /// ```
@@ -97,58 +373,361 @@ pub fn xicorf<F: FloatCore>(x: &[F], y: &[F]) -> f64 {
 ///
 /// assert_eq!(xi, 0.9375);
 /// ```
-pub fn xicor<T: Ord + Copy>(x: &[T], y: &[T]) -> f64 {
+pub fn xicor<X: Ord, Y: Ord>(x: &[X], y: &[Y]) -> f64 {
     assert!(x.len() == y.len(), "x and y must have the same length");
 
-    let idcs = argsort(x);
-    let y_ord = permute(y, &idcs);
+    ranks(x, y).xi()
+}
+
+/// Calculate the xi-correlation of two sequences, ordering their values with
+/// the given key extraction functions.
+///
+/// This is the analogue of [`slice::sort_by_key`]. The keys are computed on
+/// demand during the sort, so no temporary key vectors are built.
+///
+/// # Example
+///
+/// ```
+/// use xicor::{xicor, xicor_by_key};
+///
+/// struct Reading { time: u32, celsius: i32 }
+///
+/// let readings: Vec<Reading> = (0..47)
+///     .map(|t| Reading { time: t, celsius: (t*t) as i32 })
+///     .collect();
+/// let xi = xicor_by_key(&readings, &readings, |r| r.time, |r| r.celsius);
+///
+/// assert_eq!(xi, 0.9375);
+/// ```
+pub fn xicor_by_key<X, Y, KX, KY, FX, FY>(
+    x: &[X],
+    y: &[Y],
+    mut key_x: FX,
+    mut key_y: FY,
+) -> f64
+where
+    KX: Ord,
+    KY: Ord,
+    FX: FnMut(&X) -> KX,
+    FY: FnMut(&Y) -> KY,
+{
+    xicor_by(
+        x,
+        y,
+        |a, b| key_x(a).cmp(&key_x(b)),
+        |a, b| key_y(a).cmp(&key_y(b)),
+    )
+}
+
+/// Calculate the xi-correlation of two sequences, ordering their values with
+/// the given comparison functions.
+///
+/// This is the analogue of [`slice::sort_by`]. The comparison functions must
+/// define total orders, exactly as for sorting. Values which compare as
+/// [`Ordering::Equal`] are treated as tied.
+///
+/// # Example
+///
+/// ```
+/// use xicor::{xicor, xicor_by};
+///
+/// let x = ["b", "A", "c", "D", "e"];
+/// let y = [2, 1, 3, 4, 5];
+///
+/// // Case-insensitive ordering of x gives perfectly increasing y
+/// let xi = xicor_by(
+///     &x,
+///     &y,
+///     |a, b| a.to_lowercase().cmp(&b.to_lowercase()),
+///     |a, b| a.cmp(b),
+/// );
+///
+/// assert_eq!(xi, xicor(&[2, 1, 3, 4, 5], &y));
+/// ```
+pub fn xicor_by<X, Y, FX, FY>(x: &[X], y: &[Y], cmp_x: FX, cmp_y: FY) -> f64
+where
+    FX: FnMut(&X, &X) -> Ordering,
+    FY: FnMut(&Y, &Y) -> Ordering,
+{
+    ranks_by(x, y, cmp_x, cmp_y).xi()
+}
+
+// Check that x and y have the same length.
+pub(super) fn check_lengths<X, Y>(x: &[X], y: &[Y]) -> Result<(), XiError> {
+    match x.len() == y.len() {
+        true => Ok(()),
+        false => Err(XiError::LengthMismatch { x: x.len(), y: y.len() }),
+    }
+}
+
+// Convert a slice of floats into a slice of OrderedFloat, which implements Ord.
+pub(super) fn as_ordered<F: FloatCore>(arr: &[F]) -> &[OrderedFloat<F>] {
+    // This is safe because OrderedFloat has transparent representation
+    unsafe { std::mem::transmute(arr) }
+}
+
+// The rank quantities from the paper, for pairs arranged in ascending order of
+// x. Only the r_i are needed individually, as the l_i only ever appear in the
+// sum of l_i(n - l_i) which forms the denominator of xi.
+pub(super) struct Ranks {
+    // r_i, the number of y values less than or equal to y_i
+    pub rs: Vec<usize>,
+    // The sum of l_i(n - l_i), where l_i is the number of y values greater than
+    // or equal to y_i
+    pub lsum: u128,
+    // The number of distinct y values
+    pub distinct: usize,
+}
 
-    let idcs = argsort(&y_ord);
-    let y_ascending = permute(&y_ord, &idcs);
-    let r_ascending = cumulative_lte(&y_ascending);
-    let l_ascending = cumulative_gte(&y_ascending);
-    let mut rs = vec![0.; x.len()];
-    let mut ls = vec![0.; x.len()];
+impl Ranks {
+    // Calculate xi exactly.
+    pub fn ratio(&self) -> XiRatio {
+        XiRatio::new(self.rs.len(), rank_diff_sum(&self.rs), self.lsum)
+    }
 
-    for ((i, r), l) in idcs.into_iter().zip(r_ascending).zip(l_ascending) {
-        rs[i] = r as f64;
-        ls[i] = l as f64;
+    // Calculate xi.
+    pub fn xi(&self) -> f64 {
+        self.ratio().to_f64()
     }
 
-    let rsum = rs.windows(2)
-        .map(|win| (win[0]-win[1]).abs())
-        .sum::<f64>();
+    // Calculate xi, returning an error if there are too few points or y is
+    // constant.
+    pub fn try_xi(&self) -> Result<f64, XiError> {
+        let n = self.rs.len();
 
-    let n = x.len() as f64;
-    let lsum = ls.into_iter()
-        .map(|l| l*(n-l))
-        .sum::<f64>();
+        if n < 2 { return Err(XiError::TooFewSamples { n }); }
+
+        // l_i(n - l_i) is zero for every point exactly when y is constant
+        if self.lsum == 0 { return Err(XiError::ConstantY); }
+
+        Ok(self.xi())
+    }
+
+    // Calculate xi given the sum of absolute differences between consecutive
+    // r_i, which may have been averaged over orderings of tied x values.
+    pub fn xi_with_sum(&self, rsum: f64) -> f64 {
+        let n = self.rs.len() as f64;
 
-    1.-n*rsum/(2.*lsum)
+        1.-n*rsum/(2.*self.lsum as f64)
+    }
+}
+
+// Compute the rank quantities, with the pairs arranged in ascending order of x.
+pub(super) fn ranks<X, Y>(x: &[X], y: &[Y]) -> Ranks
+where
+    X: Ord,
+    Y: Ord,
+{
+    assert!(x.len() == y.len(), "x and y must have the same length");
+
+    let mut rs = vec![];
+    let mut bufs = RadixBuffers::default();
+
+    if let Some((lsum, distinct)) = radix_ranks(x, y, &mut bufs, &mut rs) {
+        return Ranks { rs, lsum, distinct };
+    }
+
+    ranks_by(x, y, X::cmp, Y::cmp)
+}
+
+// Compute the rank quantities, with x and y ordered by the given comparison
+// functions rather than their Ord implementations.
+pub(super) fn ranks_by<X, Y, FX, FY>(
+    x: &[X],
+    y: &[Y],
+    cmp_x: FX,
+    mut cmp_y: FY,
+) -> Ranks
+where
+    FX: FnMut(&X, &X) -> Ordering,
+    FY: FnMut(&Y, &Y) -> Ordering,
+{
+    assert!(x.len() == y.len(), "x and y must have the same length");
+
+    let idcs = argsort_by(x, cmp_x);
+
+    ranks_ordered_by(&permute(y, &idcs), |a, b| cmp_y(a, b))
+}
+
+// Compute the rank quantities for y values that have already been arranged in
+// ascending order of x.
+pub(super) fn ranks_ordered<T: Ord>(y_ord: &[T]) -> Ranks {
+    ranks_sorted_by(y_ord, &argsort(y_ord), T::eq)
+}
+
+// Compute the rank quantities for y values that have already been arranged in
+// ascending order of x, comparing them with the given function.
+pub(super) fn ranks_ordered_by<T, F>(y_ord: &[T], mut cmp: F) -> Ranks
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let idcs = argsort_by(y_ord, &mut cmp);
+
+    ranks_sorted_by(y_ord, &idcs, |a, b| cmp(a, b).is_eq())
+}
+
+// Compute the rank quantities for y values that have already been arranged in
+// ascending order of x, given the indices which sort them.
+//
+// This is a single pass over the runs of equal values in sorted order. Every
+// value in a run spanning sorted positions start..end has r = end and
+// l = n - start, so r is written straight into x order and l is only ever
+// accumulated, without materialising any sorted copies of the data.
+fn ranks_sorted_by<T, F>(y_ord: &[T], idcs: &[usize], mut eq: F) -> Ranks
+where
+    F: FnMut(&T, &T) -> bool,
+{
+    let n = y_ord.len();
+    let mut rs = vec![0; n];
+    let mut lsum = 0;
+    let mut distinct = 0;
+    let mut start = 0;
+
+    for end in 1..=n {
+        if end < n && eq(&y_ord[idcs[end-1]], &y_ord[idcs[end]]) { continue; }
+
+        let l = (n-start) as u128;
+
+        for &i in &idcs[start..end] { rs[i] = end; }
+
+        lsum += (end-start) as u128*l*(n as u128-l);
+        distinct += 1;
+        start = end;
+    }
+
+    Ranks { rs, lsum, distinct }
+}
+
+// The greatest common divisor of a and b, which is zero if both are.
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 { (a, b) = (b, a%b); }
+
+    a
+}
+
+// Sum the absolute differences between consecutive r_i. This is the only part
+// of xi which depends on the ordering of the pairs by x.
+pub(super) fn rank_diff_sum(rs: &[usize]) -> u128 {
+    rs.windows(2)
+        .map(|win| win[0].abs_diff(win[1]) as u128)
+        .sum::<u128>()
+}
+
+// Expected sum of absolute differences between consecutive r_i, over every
+// ordering of the pairs with tied x values. Both arrays must be arranged in
+// ascending order of x.
+//
+// Within a group of m tied x values, each of the m-1 consecutive pairs is a
+// uniformly random pair of distinct group members. Across the boundary between
+// two groups, the pair is an independent uniform choice from each group. Both
+// expectations only need the sorted r values of the groups.
+pub(super) fn expected_rank_diff_sum<T: Ord>(x_ord: &[T], rs: &[usize]) -> f64 {
+    let mut total = 0.;
+    let mut prev: Vec<f64> = vec![];
+    let mut start = 0;
+
+    while start < x_ord.len() {
+        let end = start+x_ord[start..].partition_point(|x| *x == x_ord[start]);
+        let mut group: Vec<f64> = rs[start..end].iter().map(|&r| r as f64).collect();
+        let m = group.len() as f64;
+
+        group.sort_unstable_by(f64::total_cmp);
+
+        // Sum of |r_a - r_b| over unordered pairs, using the fact that the i-th
+        // smallest value is larger than i others and smaller than m-1-i others
+        let within = group.iter()
+            .enumerate()
+            .map(|(i, r)| r*(2.*i as f64-m+1.))
+            .sum::<f64>();
+
+        if m > 1. { total += 2.*within/m; }
+        if !prev.is_empty() { total += mean_abs_diff(&prev, &group); }
+
+        prev = group;
+        start = end;
+    }
+
+    total
+}
+
+// Mean of |a - b| over every a in the first array and b in the second, both of
+// which must be sorted.
+fn mean_abs_diff(a: &[f64], b: &[f64]) -> f64 {
+    let a_total = a.iter().sum::<f64>();
+    let mut below = 0;
+    let mut below_sum = 0.;
+    let mut sum = 0.;
+
+    for &v in b {
+        while below < a.len() && a[below] <= v {
+            below_sum += a[below];
+            below += 1;
+        }
+
+        let above = (a.len()-below) as f64;
+
+        sum += v*below as f64-below_sum+(a_total-below_sum)-v*above;
+    }
+
+    sum/(a.len()*b.len()) as f64
+}
+
+// Return the indices that would sort the given array, with ties ordered as
+// dictated by the tie policy. For the expected policy the ties are kept in
+// input order, as the caller averages over their orderings.
+pub(super) fn argsort_ties<T: Ord>(arr: &[T], ties: TiePolicy) -> Vec<usize> {
+    let mut idcs = argsort(arr);
+
+    if let TiePolicy::Random(seed) = ties {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut start = 0;
+
+        while start < idcs.len() {
+            let first = &arr[idcs[start]];
+            let len = idcs[start..].partition_point(|&i| arr[i] == *first);
+
+            idcs[start..start+len].shuffle(&mut rng);
+            start += len;
+        }
+    }
+
+    idcs
 }
 
 // Return the indices that would sort the given array. That is, if you map the
 // returned sequence of indices i -> arr[i], the resulting sequence is sorted.
+// Equal elements keep their relative order from the input. Primitive integers
+// and floats are radix sorted, and everything else comparison sorted.
 pub(super) fn argsort<T: Ord>(arr: &[T]) -> Vec<usize> {
+    radix_argsort(arr).unwrap_or_else(|| argsort_by(arr, T::cmp))
+}
+
+// Return the indices that would sort the given array according to the given
+// comparison function. Equal elements keep their relative order.
+pub(super) fn argsort_by<T, F>(arr: &[T], mut cmp: F) -> Vec<usize>
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
     let mut idcs: Vec<usize> = (0..arr.len()).collect();
 
-    idcs.sort_unstable_by_key(|&i| &arr[i]);
+    idcs.sort_unstable_by(|&i, &j| cmp(&arr[i], &arr[j]).then(i.cmp(&j)));
     idcs
 }
 
-// Permute the given array such that arr[n] ends up at idcs[n].
-pub(super) fn permute<T: Copy>(arr: &[T], idcs: &[usize]) -> Vec<T> {
+// Permute the given array such that arr[idcs[n]] ends up at n. The elements
+// are borrowed rather than copied, so any type can be permuted.
+pub(super) fn permute<'a, T>(arr: &'a [T], idcs: &[usize]) -> Vec<&'a T> {
     idcs.iter()
-        .map(|&i| arr[i])
+        .map(|&i| &arr[i])
         .collect()
 }
 
 // For every element in the array, count how many elements are less than or
 // equal to it. The array should be sorted before it is passed in.
-pub(super) fn cumulative_lte<T: PartialEq<T> + Copy>(arr: &[T]) -> Vec<usize> {
+pub(super) fn cumulative_lte<T: PartialEq<T>>(arr: &[T]) -> Vec<usize> {
     let mut counts: Vec<usize> = (1..=arr.len()).collect();
 
-    for i in (0..arr.len()-1).rev() {
+    for i in (0..arr.len().saturating_sub(1)).rev() {
         if arr[i] == arr[i+1] { counts[i] = counts[i+1]; }
     }
 
@@ -157,10 +736,10 @@ pub(super) fn cumulative_lte<T: PartialEq<T> + Copy>(arr: &[T]) -> Vec<usize> {
 
 // For every element in the array, count how many elements are greater than or
 // equal to it. The array should be sorted before it is passed in.
-pub(super) fn cumulative_gte<T: PartialEq<T> + Copy>(arr: &[T]) -> Vec<usize> {
+pub(super) fn cumulative_gte<T: PartialEq<T>>(arr: &[T]) -> Vec<usize> {
     let mut counts: Vec<usize> = (1..=arr.len()).rev().collect();
 
-    for i in 0..arr.len()-1 {
+    for i in 0..arr.len().saturating_sub(1) {
         if arr[i+1] == arr[i] { counts[i+1] = counts[i]; }
     }
 